@@ -0,0 +1,111 @@
+//! Asset pack format, shared by the engine and the `pack` tool.
+//! It doesn't depend on the engine, so the tool builds without SDL2.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub const MAGIC: &[u8; 4] = b"EPAK";
+pub const PACK_VERSION: u32 = 1;
+/// magic, version and manifest length
+pub const HEADER_LEN: u64 = 12;
+/// deflate level used by `write_pack`, 0 to 10
+const COMPRESSION_LEVEL: u8 = 8;
+
+/// File stored in a pack.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PackEntry {
+    pub path: String,
+    /// position of the stored bytes, from the end of the manifest
+    pub offset: u64,
+    /// stored size, compressed or not
+    pub size: u64,
+    pub original_size: u64,
+    /// deflate compressed. files that don't get smaller, such as PNG, are stored as is.
+    pub compressed: bool,
+    /// SHA-256 of the original contents, in hex
+    pub hash: String,
+}
+
+/// List of the files in a pack, stored as JSON at the start of the pack.
+///
+/// Layout: `EPAK`, version (u32 LE), manifest length (u32 LE), manifest JSON, file data.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PackManifest {
+    pub version: u32,
+    pub files: Vec<PackEntry>,
+}
+
+/// SHA-256 of *contents* in hex.
+pub fn hash(contents: &[u8]) -> String {
+    Sha256::digest(contents)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Every file under *root*, relative to it with `/` separators, sorted.
+fn files(root: &Path) -> io::Result<Vec<String>> {
+    let mut files = Vec::new();
+    let mut directories = vec![root.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        for entry in fs::read_dir(&directory)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                directories.push(path);
+            } else if let Ok(relative) = path.strip_prefix(root) {
+                let parts: Vec<_> = relative
+                    .components()
+                    .map(|part| part.as_os_str().to_string_lossy())
+                    .collect();
+                files.push(parts.join("/"));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Bundle every file under *source* into a pack at *output*.
+/// Paths in the pack are relative to *source*, so mount it where *source* was.
+pub fn write_pack(source: &Path, output: &Path) -> io::Result<PackManifest> {
+    let mut data = Vec::new();
+    let mut files_in_pack = Vec::new();
+    for path in files(source)? {
+        let contents = fs::read(source.join(&path))?;
+        let compressed = miniz_oxide::deflate::compress_to_vec(&contents, COMPRESSION_LEVEL);
+        let is_compressed = compressed.len() < contents.len();
+        let stored = if is_compressed {
+            &compressed
+        } else {
+            &contents
+        };
+
+        files_in_pack.push(PackEntry {
+            path,
+            offset: data.len() as u64,
+            size: stored.len() as u64,
+            original_size: contents.len() as u64,
+            compressed: is_compressed,
+            hash: hash(&contents),
+        });
+        data.extend_from_slice(stored);
+    }
+
+    let manifest = PackManifest {
+        version: PACK_VERSION,
+        files: files_in_pack,
+    };
+    let manifest_json = serde_json::to_vec(&manifest)?;
+
+    let mut file = fs::File::create(output)?;
+    file.write_all(MAGIC)?;
+    file.write_all(&PACK_VERSION.to_le_bytes())?;
+    file.write_all(&(manifest_json.len() as u32).to_le_bytes())?;
+    file.write_all(&manifest_json)?;
+    file.write_all(&data)?;
+    Ok(manifest)
+}
@@ -0,0 +1,38 @@
+use std::path::Path;
+
+use asset_pack::write_pack;
+
+/// Bundle an asset directory into a pack for the engine's `Vfs::mount_pack`.
+///
+/// usage: `pack [source directory] [output file]`, `assets` and `assets.pack` by default.
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let source = args.first().map_or("assets", String::as_str);
+    let output = args.get(1).map_or("assets.pack", String::as_str);
+
+    let manifest = match write_pack(Path::new(source), Path::new(output)) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            eprintln!("failed to pack {source} : {err}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut original_total = 0;
+    let mut stored_total = 0;
+    for entry in manifest.files.iter() {
+        println!(
+            "{} {:>10} -> {:>10} {}",
+            &entry.hash[..16],
+            entry.original_size,
+            entry.size,
+            entry.path
+        );
+        original_total += entry.original_size;
+        stored_total += entry.size;
+    }
+    println!(
+        "packed {} files into {output}, {original_total} -> {stored_total} bytes",
+        manifest.files.len()
+    );
+}
@@ -0,0 +1,157 @@
+//! Spatial index versus linear scan, for growing entity counts.
+//!
+//! Run with `cargo bench -p engine --bench spatial`.
+
+use std::cell::RefCell;
+use std::hint::black_box;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use engine::game::entity::{Entity, EntityDrawInfo, EntityTrait};
+use engine::game::scene::Scene;
+use engine::game::spatial::SpatialGrid;
+use engine::random::Rng;
+use engine::types::*;
+
+const ENTITY_SIZE: i32 = 32;
+const QUERIES: usize = 1000;
+const TICKS: usize = 100;
+
+/// entity without a sprite, it only has bounds
+struct Dot {
+    pos: Vec2,
+}
+
+impl EntityTrait for Dot {
+    fn pos(&self) -> Vec2 {
+        self.pos
+    }
+
+    fn set_pos(&mut self, new_pos: Vec2) {
+        self.pos = new_pos;
+    }
+
+    fn update(&mut self) {}
+
+    fn get_draw_info(&self) -> EntityDrawInfo {
+        unreachable!("benchmark entities are never drawn")
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        Some(Rect::from_center_size(
+            self.pos,
+            Vec2 {
+                x: ENTITY_SIZE,
+                y: ENTITY_SIZE,
+            },
+        ))
+    }
+}
+
+fn bounds(entity: &Entity) -> Rect {
+    entity.borrow().bounds().unwrap()
+}
+
+/// average time of running *f* once per item of *inputs*
+fn measure<T, F>(inputs: &[T], mut f: F) -> Duration
+where
+    F: FnMut(&T),
+{
+    let start = Instant::now();
+    for input in inputs {
+        f(input);
+    }
+    start.elapsed() / inputs.len() as u32
+}
+
+fn main() {
+    let mut rng = Rng::new(42);
+    println!(
+        "{:>8} | {:>12} {:>12} | {:>12} {:>12} | {:>12} | {:>12} {:>12}",
+        "entities",
+        "view scan",
+        "view grid",
+        "near scan",
+        "near grid",
+        "move grid",
+        "scene tick",
+        "rescan"
+    );
+
+    for count in [1_000, 10_000, 50_000, 100_000] {
+        // constant density: about one entity per 128x128 area
+        let side = (count as f32).sqrt() as i32 * 128;
+        let world = Rect {
+            x: -side / 2,
+            y: -side / 2,
+            w: side,
+            h: side,
+        };
+        let entities: Vec<Entity> = (0..count)
+            .map(|_| {
+                let entity: Entity = Rc::new(RefCell::new(Dot {
+                    pos: rng.point_in_rect(world),
+                }));
+                entity
+            })
+            .collect();
+        let mut grid = SpatialGrid::default();
+        for entity in entities.iter() {
+            grid.update(entity, bounds(entity));
+        }
+
+        let views: Vec<Rect> = (0..QUERIES)
+            .map(|_| Rect::from_center_size(rng.point_in_rect(world), Vec2 { x: 1600, y: 1200 }))
+            .collect();
+        let points: Vec<Vec2> = (0..QUERIES).map(|_| rng.point_in_rect(world)).collect();
+
+        let view_scan = measure(&views, |view| {
+            let visible = entities
+                .iter()
+                .filter(|entity| bounds(entity).collides(view))
+                .count();
+            black_box(visible);
+        });
+        let view_grid = measure(&views, |view| {
+            black_box(grid.query_rect(*view).len());
+        });
+
+        let near_scan = measure(&points, |point| {
+            let nearest = entities.iter().min_by_key(|entity| {
+                let offset = entity.borrow().pos() - *point;
+                offset.x as i64 * offset.x as i64 + offset.y as i64 * offset.y as i64
+            });
+            black_box(nearest.is_some());
+        });
+        let near_grid = measure(&points, |point| {
+            black_box(grid.nearest(*point, None, |_| true).is_some());
+        });
+
+        // every entity moves a little, as in a tick of a busy scene
+        let start = Instant::now();
+        for entity in entities.iter() {
+            let pos = entity.borrow().pos();
+            let step = Vec2 {
+                x: rng.range_i32(-4, 5),
+                y: rng.range_i32(-4, 5),
+            };
+            entity.borrow_mut().set_pos(pos + step);
+            grid.update(entity, bounds(entity));
+        }
+        let move_grid = start.elapsed();
+
+        // a tick of a scene where nothing moves only compares bounds
+        let mut scene = Scene::new();
+        for entity in entities.iter() {
+            scene.add_entity(entity.clone());
+        }
+        let ticks = vec![Duration::from_millis(16); TICKS];
+        let scene_tick = measure(&ticks, |delta| scene.update(*delta));
+        let rescan = measure(&ticks, |_| scene.refresh_spatial());
+
+        println!(
+            "{:>8} | {:>12?} {:>12?} | {:>12?} {:>12?} | {:>12?} | {:>12?} {:>12?}",
+            count, view_scan, view_grid, near_scan, near_grid, move_grid, scene_tick, rescan
+        );
+    }
+}
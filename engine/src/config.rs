@@ -0,0 +1,96 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::locale;
+use crate::types::*;
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub enum WindowMode {
+    #[default]
+    Windowed,
+    /// exclusive fullscreen
+    Fullscreen,
+    /// borderless window covering the whole desktop
+    Borderless,
+}
+
+/// Window and display configuration passed to `Engine::new`.
+/// Missing keys in the settings file fall back to the default.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct EngineConfig {
+    pub title: String,
+    /// window size in window coordinates
+    pub size: Vec2,
+    pub min_size: Option<Vec2>,
+    pub window_mode: WindowMode,
+    pub vsync: bool,
+    /// maximum frames per second. `None` for unlimited.
+    pub frame_rate_cap: Option<u32>,
+    /// image file used as window icon
+    pub icon: Option<PathBuf>,
+    /// language of the string tables, such as `en`
+    pub language: String,
+
+    /// file the settings are saved to when changed at runtime
+    #[serde(skip)]
+    pub settings_path: Option<PathBuf>,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            title: String::from("example title"),
+            size: Vec2 { x: 800, y: 600 },
+            min_size: None,
+            window_mode: WindowMode::Windowed,
+            vsync: true,
+            frame_rate_cap: None,
+            icon: None,
+            language: String::from(locale::FALLBACK_LANGUAGE),
+            settings_path: None,
+        }
+    }
+}
+
+impl EngineConfig {
+    /// Load settings from JSON file at *path*.
+    /// Changes at runtime are saved back to *path*.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let config_str = fs::read_to_string(path).map_err(|_| Error::FileReadFailure)?;
+        let mut config: EngineConfig =
+            serde_json::from_str(config_str.as_str()).map_err(|_| Error::JSONParseFailure)?;
+        config.settings_path = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    /// Load settings from *path*, or use the default if the file does not exist.
+    /// Changes at runtime are saved to *path* in both cases.
+    pub fn load_or_default(path: &Path) -> Result<Self, Error> {
+        if path.exists() {
+            Self::load(path)
+        } else {
+            Ok(Self {
+                settings_path: Some(path.to_path_buf()),
+                ..Self::default()
+            })
+        }
+    }
+
+    /// Save settings to JSON file at *path*.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let config_str = serde_json::to_string_pretty(self).map_err(|_| Error::JSONParseFailure)?;
+        fs::write(path, config_str).map_err(|_| Error::FileWriteFailure)
+    }
+
+    /// Save settings to `settings_path` if it exists.
+    pub(crate) fn persist(&self) -> Result<(), Error> {
+        match &self.settings_path {
+            Some(path) => self.save(path),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::game::scene::Scene;
+use crate::render::primitive::Space;
+use crate::render::Renderer;
+use crate::types::*;
+
+/// number of frames shown in the frame time graph
+const FRAME_HISTORY: usize = 120;
+
+/// frame time of 60 FPS, drawn as reference line in the graph
+const TARGET_FRAME_MS: f32 = 1000.0 / 60.0;
+
+/// Runtime-toggleable debug overlay.
+/// F3 toggles the overlay, F4 toggles entity bounds.
+/// Everything is off by default in release builds.
+pub struct DebugOverlay {
+    /// show FPS, frame time graph, statistics and crosshair.
+    pub enabled: bool,
+    /// show entity bounding boxes and collider outlines.
+    pub show_bounds: bool,
+
+    frame_times: VecDeque<Duration>,
+}
+
+impl Default for DebugOverlay {
+    fn default() -> Self {
+        Self {
+            enabled: cfg!(debug_assertions),
+            show_bounds: cfg!(debug_assertions),
+            frame_times: VecDeque::with_capacity(FRAME_HISTORY),
+        }
+    }
+}
+
+impl DebugOverlay {
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+    }
+
+    pub fn toggle_bounds(&mut self) {
+        self.show_bounds = !self.show_bounds;
+    }
+
+    /// Record the time taken by the last frame.
+    pub fn record_frame(&mut self, frame_time: Duration) {
+        if self.frame_times.len() == FRAME_HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+    }
+
+    /// average FPS over the recorded frames
+    pub fn fps(&self) -> f32 {
+        let total: Duration = self.frame_times.iter().sum();
+        if total.is_zero() {
+            0.0
+        } else {
+            self.frame_times.len() as f32 / total.as_secs_f32()
+        }
+    }
+
+    pub(crate) fn draw(&self, renderer: &mut Renderer, scene: &Scene) {
+        if self.show_bounds {
+            self.draw_bounds(renderer, scene);
+        }
+        if self.enabled {
+            self.draw_crosshair(renderer);
+            self.draw_stats(renderer, scene);
+            self.draw_graph(renderer);
+        }
+    }
+
+    fn draw_bounds(&self, renderer: &mut Renderer, scene: &Scene) {
+        let camera = &scene.scene_info().camera;
+        for entity in scene.entities().iter() {
+            let entity = entity.borrow();
+            let world_rect = entity.get_draw_info().world_rect;
+            renderer.draw_rect(Space::World(camera), world_rect, Color::BLUE, 1);
+            if let Some(collider) = entity.collider() {
+                renderer.draw_rect(Space::World(camera), collider, Color::GREEN, 1);
+            }
+        }
+    }
+
+    fn draw_crosshair(&self, renderer: &mut Renderer) {
+        let center = renderer.render_info.screen_size / 2;
+        renderer.draw_line(
+            Space::Screen,
+            center + Vec2 { x: -10, y: 0 },
+            center + Vec2 { x: 10, y: 0 },
+            Color::MAGENTA,
+            1,
+        );
+        renderer.draw_line(
+            Space::Screen,
+            center + Vec2 { x: 0, y: -10 },
+            center + Vec2 { x: 0, y: 10 },
+            Color::MAGENTA,
+            1,
+        );
+    }
+
+    fn draw_stats(&self, renderer: &mut Renderer, scene: &Scene) {
+        let camera = &scene.scene_info().camera;
+        let camera_pos = camera.position();
+        let lines = [
+            format!("{:.0} FPS", self.fps()),
+            format!("entities {}", scene.entities().len()),
+            format!(
+                "sprites {} culled {}",
+                renderer.render_info.sprites, renderer.render_info.culled
+            ),
+            format!("draw calls {}", renderer.render_info.draw_calls),
+            format!(
+                "texture memory {} KB",
+                renderer.texture_manager.memory_usage() / 1024
+            ),
+            format!("camera {} {}", camera_pos.x, camera_pos.y),
+            format!("zoom {:.2}", camera.zoom()),
+        ];
+
+        let glyph = Vec2 { x: 8, y: 16 };
+        let width = lines.iter().map(|line| line.len()).max().unwrap_or(0) as i32 * glyph.x;
+        renderer.fill_rect(
+            Space::Screen,
+            Rect {
+                x: 0,
+                y: 0,
+                w: width + 8,
+                h: lines.len() as i32 * glyph.y + 8,
+            },
+            Color::rgba(0, 0, 0, 160),
+        );
+
+        let Some(font) = renderer.fonts.first().cloned() else {
+            return;
+        };
+        for (idx, line) in lines.iter().enumerate() {
+            font.draw(
+                &mut renderer.canvas,
+                line,
+                Vec2 {
+                    x: 4,
+                    y: 4 + idx as i32 * glyph.y,
+                },
+                glyph,
+            );
+        }
+    }
+
+    /// frame time graph at the left bottom of the screen.
+    /// 1 pixel per millisecond.
+    fn draw_graph(&self, renderer: &mut Renderer) {
+        let screen_size = renderer.render_info.screen_size;
+        let bar_width = 2;
+        let graph = Rect {
+            x: 0,
+            y: screen_size.y - 64,
+            w: FRAME_HISTORY as i32 * bar_width,
+            h: 64,
+        };
+        renderer.fill_rect(Space::Screen, graph, Color::rgba(0, 0, 0, 160));
+
+        for (idx, frame_time) in self.frame_times.iter().enumerate() {
+            let ms = frame_time.as_secs_f32() * 1000.0;
+            let h = (ms.round() as i32).min(graph.h);
+            let color = if ms > TARGET_FRAME_MS {
+                Color::RED
+            } else {
+                Color::GREEN
+            };
+            renderer.fill_rect(
+                Space::Screen,
+                Rect {
+                    x: graph.x + idx as i32 * bar_width,
+                    y: graph.y + graph.h - h,
+                    w: bar_width,
+                    h,
+                },
+                color,
+            );
+        }
+
+        let target_y = graph.y + graph.h - TARGET_FRAME_MS.round() as i32;
+        renderer.draw_line(
+            Space::Screen,
+            Vec2 {
+                x: graph.x,
+                y: target_y,
+            },
+            Vec2 {
+                x: graph.x + graph.w,
+                y: target_y,
+            },
+            Color::WHITE,
+            1,
+        );
+    }
+}
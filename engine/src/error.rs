@@ -2,7 +2,14 @@
 pub enum Error {
     InitFailure(String),
     FileReadFailure,
+    FileWriteFailure,
     JSONParseFailure,
     TextureCreateFailure,
     AlreadyExists,
+    ScriptCompileFailure(String),
+    UnsupportedVersion(u32),
+    HierarchyCycle,
+    UnknownBehavior(String),
+    HashMismatch(String),
+    InvalidPlayer(usize),
 }
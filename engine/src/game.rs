@@ -1,4 +1,14 @@
 pub mod scene;
 
+pub mod ai;
 pub mod entity;
+pub mod hierarchy;
+pub mod lifecycle;
+pub mod navigation;
+pub mod particle;
+pub mod save;
+pub mod scheduler;
+pub mod script;
+pub mod spatial;
+pub mod tween;
 pub mod ui;
@@ -0,0 +1,2 @@
+pub mod behavior;
+pub mod fsm;
@@ -0,0 +1,494 @@
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::Error;
+use crate::vfs::Vfs;
+
+/// Result of ticking a node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    Failure,
+    /// not finished, tick again on the next update
+    Running,
+}
+
+/// Values shared by the nodes of a tree and the game.
+#[derive(Clone, Default, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Blackboard {
+    values: BTreeMap<String, Value>,
+}
+
+impl Blackboard {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.values.get(key)
+    }
+
+    pub fn set(&mut self, key: &str, value: impl Into<Value>) {
+        self.values.insert(key.to_string(), value.into());
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        self.values.remove(key)
+    }
+
+    pub fn get_bool(&self, key: &str) -> bool {
+        self.get(key).and_then(Value::as_bool).unwrap_or(false)
+    }
+
+    pub fn get_f64(&self, key: &str) -> Option<f64> {
+        self.get(key).and_then(Value::as_f64)
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(Value::as_str)
+    }
+
+    /// Set, and neither `null` nor `false`.
+    fn is_set(&self, key: &str) -> bool {
+        !matches!(
+            self.get(key),
+            None | Some(Value::Null) | Some(Value::Bool(false))
+        )
+    }
+}
+
+/// Behavior tree definition, usually loaded from a JSON file.
+/// ```json
+/// { "type": "selector", "children": [
+///     { "type": "sequence", "children": [
+///         { "type": "condition", "name": "sees_player" },
+///         { "type": "action", "name": "chase", "params": { "speed": 4 } }
+///     ] },
+///     { "type": "action", "name": "wander" }
+/// ] }
+/// ```
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NodeDef {
+    /// children in order until one fails
+    Sequence { children: Vec<NodeDef> },
+    /// children in order until one succeeds
+    Selector { children: Vec<NodeDef> },
+    /// every child each tick. fails when one fails, succeeds when all succeed.
+    Parallel { children: Vec<NodeDef> },
+    /// swaps success and failure
+    Inverter { child: Box<NodeDef> },
+    /// succeeds when the child finishes, even if it failed
+    Succeeder { child: Box<NodeDef> },
+    /// runs the child *times* successes in a row, or forever. fails when the child fails.
+    Repeat {
+        child: Box<NodeDef>,
+        #[serde(default)]
+        times: Option<u32>,
+    },
+    /// runs the child until it succeeds, at most *times* tries if given
+    Retry {
+        child: Box<NodeDef>,
+        #[serde(default)]
+        times: Option<u32>,
+    },
+    /// fails for *ticks* ticks after the child finishes
+    Cooldown { child: Box<NodeDef>, ticks: u32 },
+    /// running for *ticks* ticks, then succeeds
+    Wait { ticks: u32 },
+    /// succeeds if *key* is set on the blackboard, or equal to *equals* if given
+    Check {
+        key: String,
+        #[serde(default)]
+        equals: Option<Value>,
+    },
+    /// sets *key* on the blackboard and succeeds
+    Set { key: String, value: Value },
+    /// condition registered with `Behaviors::condition`
+    Condition {
+        name: String,
+        #[serde(default)]
+        params: Value,
+    },
+    /// action registered with `Behaviors::action`
+    Action {
+        name: String,
+        #[serde(default)]
+        params: Value,
+    },
+}
+
+impl NodeDef {
+    /// Load the definition at the virtual *path*.
+    pub fn load(vfs: &Vfs, path: &str) -> Result<Self, Error> {
+        let json = vfs.read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|_| Error::JSONParseFailure)
+    }
+}
+
+type ActionFn<C> = Rc<dyn Fn(&mut C, &mut Blackboard, &Value) -> Status>;
+type ConditionFn<C> = Rc<dyn Fn(&C, &Blackboard, &Value) -> bool>;
+
+/// Named actions and conditions that tree definitions refer to.
+/// The context *C* is what the tree controls, such as the data of an entity.
+pub struct Behaviors<C> {
+    actions: HashMap<String, ActionFn<C>>,
+    conditions: HashMap<String, ConditionFn<C>>,
+}
+
+impl<C> Default for Behaviors<C> {
+    fn default() -> Self {
+        Self {
+            actions: HashMap::new(),
+            conditions: HashMap::new(),
+        }
+    }
+}
+
+impl<C> Behaviors<C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the action *name*. It gets the `params` of the node.
+    pub fn action<F>(mut self, name: &str, action: F) -> Self
+    where
+        F: Fn(&mut C, &mut Blackboard, &Value) -> Status + 'static,
+    {
+        self.actions.insert(name.to_string(), Rc::new(action));
+        self
+    }
+
+    /// Register the condition *name*. It gets the `params` of the node.
+    pub fn condition<F>(mut self, name: &str, condition: F) -> Self
+    where
+        F: Fn(&C, &Blackboard, &Value) -> bool + 'static,
+    {
+        self.conditions.insert(name.to_string(), Rc::new(condition));
+        self
+    }
+}
+
+enum Node<C> {
+    Sequence {
+        children: Vec<Node<C>>,
+        current: usize,
+    },
+    Selector {
+        children: Vec<Node<C>>,
+        current: usize,
+    },
+    Parallel {
+        children: Vec<(Node<C>, Option<Status>)>,
+    },
+    Inverter(Box<Node<C>>),
+    Succeeder(Box<Node<C>>),
+    Repeat {
+        child: Box<Node<C>>,
+        times: Option<u32>,
+        count: u32,
+    },
+    Retry {
+        child: Box<Node<C>>,
+        times: Option<u32>,
+        count: u32,
+    },
+    Cooldown {
+        child: Box<Node<C>>,
+        ticks: u32,
+        remaining: u32,
+    },
+    Wait {
+        ticks: u32,
+        elapsed: u32,
+    },
+    Check {
+        key: String,
+        equals: Option<Value>,
+    },
+    Set {
+        key: String,
+        value: Value,
+    },
+    Condition(ConditionFn<C>, Value),
+    Action(ActionFn<C>, Value),
+}
+
+impl<C> Node<C> {
+    fn build(def: &NodeDef, behaviors: &Behaviors<C>) -> Result<Self, Error> {
+        let build_all = |children: &[NodeDef]| {
+            children
+                .iter()
+                .map(|child| Node::build(child, behaviors))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let build_one = |child: &NodeDef| Node::build(child, behaviors).map(Box::new);
+
+        Ok(match def {
+            NodeDef::Sequence { children } => Node::Sequence {
+                children: build_all(children)?,
+                current: 0,
+            },
+            NodeDef::Selector { children } => Node::Selector {
+                children: build_all(children)?,
+                current: 0,
+            },
+            NodeDef::Parallel { children } => Node::Parallel {
+                children: build_all(children)?
+                    .into_iter()
+                    .map(|child| (child, None))
+                    .collect(),
+            },
+            NodeDef::Inverter { child } => Node::Inverter(build_one(child)?),
+            NodeDef::Succeeder { child } => Node::Succeeder(build_one(child)?),
+            NodeDef::Repeat { child, times } => Node::Repeat {
+                child: build_one(child)?,
+                times: *times,
+                count: 0,
+            },
+            NodeDef::Retry { child, times } => Node::Retry {
+                child: build_one(child)?,
+                times: *times,
+                count: 0,
+            },
+            NodeDef::Cooldown { child, ticks } => Node::Cooldown {
+                child: build_one(child)?,
+                ticks: *ticks,
+                remaining: 0,
+            },
+            NodeDef::Wait { ticks } => Node::Wait {
+                ticks: *ticks,
+                elapsed: 0,
+            },
+            NodeDef::Check { key, equals } => Node::Check {
+                key: key.clone(),
+                equals: equals.clone(),
+            },
+            NodeDef::Set { key, value } => Node::Set {
+                key: key.clone(),
+                value: value.clone(),
+            },
+            NodeDef::Condition { name, params } => {
+                let condition = behaviors
+                    .conditions
+                    .get(name)
+                    .ok_or_else(|| Error::UnknownBehavior(name.clone()))?;
+                Node::Condition(condition.clone(), params.clone())
+            }
+            NodeDef::Action { name, params } => {
+                let action = behaviors
+                    .actions
+                    .get(name)
+                    .ok_or_else(|| Error::UnknownBehavior(name.clone()))?;
+                Node::Action(action.clone(), params.clone())
+            }
+        })
+    }
+
+    /// Tick the node. A node that finishes resets itself, so it starts over next time.
+    fn tick(&mut self, context: &mut C, blackboard: &mut Blackboard) -> Status {
+        match self {
+            Node::Sequence { children, current } => {
+                tick_composite(children, current, Status::Success, context, blackboard)
+            }
+            Node::Selector { children, current } => {
+                tick_composite(children, current, Status::Failure, context, blackboard)
+            }
+            Node::Parallel { children } => {
+                for (child, status) in children.iter_mut() {
+                    if status.is_none() {
+                        *status = match child.tick(context, blackboard) {
+                            Status::Running => None,
+                            finished => Some(finished),
+                        };
+                    }
+                }
+                let result = if children
+                    .iter()
+                    .any(|(_, status)| *status == Some(Status::Failure))
+                {
+                    Status::Failure
+                } else if children.iter().all(|(_, status)| status.is_some()) {
+                    Status::Success
+                } else {
+                    return Status::Running;
+                };
+                for (child, status) in children.iter_mut() {
+                    // children still running are interrupted
+                    child.reset();
+                    *status = None;
+                }
+                result
+            }
+            Node::Inverter(child) => match child.tick(context, blackboard) {
+                Status::Success => Status::Failure,
+                Status::Failure => Status::Success,
+                Status::Running => Status::Running,
+            },
+            Node::Succeeder(child) => match child.tick(context, blackboard) {
+                Status::Running => Status::Running,
+                _ => Status::Success,
+            },
+            Node::Repeat {
+                child,
+                times,
+                count,
+            } => match child.tick(context, blackboard) {
+                Status::Success => {
+                    *count += 1;
+                    if times.is_some_and(|times| *count >= times) {
+                        *count = 0;
+                        Status::Success
+                    } else {
+                        Status::Running
+                    }
+                }
+                Status::Failure => {
+                    *count = 0;
+                    Status::Failure
+                }
+                Status::Running => Status::Running,
+            },
+            Node::Retry {
+                child,
+                times,
+                count,
+            } => match child.tick(context, blackboard) {
+                Status::Success => {
+                    *count = 0;
+                    Status::Success
+                }
+                Status::Failure => {
+                    *count += 1;
+                    if times.is_some_and(|times| *count >= times) {
+                        *count = 0;
+                        Status::Failure
+                    } else {
+                        Status::Running
+                    }
+                }
+                Status::Running => Status::Running,
+            },
+            Node::Cooldown {
+                child,
+                ticks,
+                remaining,
+            } => {
+                if *remaining > 0 {
+                    *remaining -= 1;
+                    return Status::Failure;
+                }
+                let status = child.tick(context, blackboard);
+                if status != Status::Running {
+                    *remaining = *ticks;
+                }
+                status
+            }
+            Node::Wait { ticks, elapsed } => {
+                *elapsed += 1;
+                if *elapsed >= *ticks {
+                    *elapsed = 0;
+                    Status::Success
+                } else {
+                    Status::Running
+                }
+            }
+            Node::Check { key, equals } => {
+                let matched = match equals {
+                    Some(equals) => blackboard.get(key) == Some(equals),
+                    None => blackboard.is_set(key),
+                };
+                if matched {
+                    Status::Success
+                } else {
+                    Status::Failure
+                }
+            }
+            Node::Set { key, value } => {
+                blackboard.set(key, value.clone());
+                Status::Success
+            }
+            Node::Condition(condition, params) => {
+                if condition(context, blackboard, params) {
+                    Status::Success
+                } else {
+                    Status::Failure
+                }
+            }
+            Node::Action(action, params) => action(context, blackboard, params),
+        }
+    }
+
+    /// Forget progress, for nodes interrupted while running.
+    fn reset(&mut self) {
+        match self {
+            Node::Sequence { children, current } | Node::Selector { children, current } => {
+                *current = 0;
+                children.iter_mut().for_each(Node::reset);
+            }
+            Node::Parallel { children } => {
+                for (child, status) in children.iter_mut() {
+                    child.reset();
+                    *status = None;
+                }
+            }
+            Node::Inverter(child) | Node::Succeeder(child) => child.reset(),
+            Node::Repeat { child, count, .. } | Node::Retry { child, count, .. } => {
+                *count = 0;
+                child.reset();
+            }
+            Node::Cooldown { child, .. } => child.reset(),
+            Node::Wait { elapsed, .. } => *elapsed = 0,
+            Node::Check { .. } | Node::Set { .. } | Node::Condition(..) | Node::Action(..) => {}
+        }
+    }
+}
+
+/// Tick *children* from *current* while they return *continue_on*.
+/// Sequences continue on success, selectors on failure.
+fn tick_composite<C>(
+    children: &mut [Node<C>],
+    current: &mut usize,
+    continue_on: Status,
+    context: &mut C,
+    blackboard: &mut Blackboard,
+) -> Status {
+    while let Some(child) = children.get_mut(*current) {
+        match child.tick(context, blackboard) {
+            Status::Running => return Status::Running,
+            status if status == continue_on => *current += 1,
+            status => {
+                *current = 0;
+                return status;
+            }
+        }
+    }
+    *current = 0;
+    continue_on
+}
+
+/// Behavior tree instance with its own progress and blackboard.
+/// Build one per entity from a shared `NodeDef` and `Behaviors`, and tick it from `update`.
+pub struct BehaviorTree<C> {
+    root: Node<C>,
+    pub blackboard: Blackboard,
+}
+
+impl<C> BehaviorTree<C> {
+    /// Fails with `Error::UnknownBehavior` if *def* names an unregistered action or condition.
+    pub fn new(def: &NodeDef, behaviors: &Behaviors<C>) -> Result<Self, Error> {
+        Ok(Self {
+            root: Node::build(def, behaviors)?,
+            blackboard: Blackboard::default(),
+        })
+    }
+
+    /// Run the tree once. The root starts over after it finishes.
+    pub fn tick(&mut self, context: &mut C) -> Status {
+        self.root.tick(context, &mut self.blackboard)
+    }
+
+    /// Interrupt running nodes and start over from the root on the next tick.
+    pub fn reset(&mut self) {
+        self.root.reset();
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use crate::error::Error;
+
+type Hook<C> = Box<dyn FnMut(&mut C)>;
+type UpdateHook<S, C> = Box<dyn FnMut(&mut C) -> Option<S>>;
+type Guard<C> = Box<dyn Fn(&C) -> bool>;
+
+/// State of a `StateMachine`, built with chained calls.
+pub struct State<S, C> {
+    parent: Option<S>,
+    initial_child: Option<S>,
+    animation: Option<String>,
+    on_enter: Option<Hook<C>>,
+    on_exit: Option<Hook<C>>,
+    on_update: Option<UpdateHook<S, C>>,
+    transitions: Vec<(Guard<C>, S)>,
+}
+
+impl<S, C> Default for State<S, C> {
+    fn default() -> Self {
+        Self {
+            parent: None,
+            initial_child: None,
+            animation: None,
+            on_enter: None,
+            on_exit: None,
+            on_update: None,
+            transitions: Vec::new(),
+        }
+    }
+}
+
+impl<S, C> State<S, C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Nest this state in *parent*. The parent stays active while this state is.
+    pub fn parent(mut self, parent: S) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    /// Child entered when a transition targets this state.
+    pub fn initial_child(mut self, child: S) -> Self {
+        self.initial_child = Some(child);
+        self
+    }
+
+    /// Animation tag to play while in this state. A child's tag takes precedence.
+    pub fn animation(mut self, tag: &str) -> Self {
+        self.animation = Some(tag.to_string());
+        self
+    }
+
+    pub fn on_enter(mut self, hook: impl FnMut(&mut C) + 'static) -> Self {
+        self.on_enter = Some(Box::new(hook));
+        self
+    }
+
+    pub fn on_exit(mut self, hook: impl FnMut(&mut C) + 'static) -> Self {
+        self.on_exit = Some(Box::new(hook));
+        self
+    }
+
+    /// Called every tick while active. Returning a state transitions to it.
+    pub fn on_update(mut self, hook: impl FnMut(&mut C) -> Option<S> + 'static) -> Self {
+        self.on_update = Some(Box::new(hook));
+        self
+    }
+
+    /// Move to *target* when *guard* is `true`. Checked before `on_update`, in the order added.
+    pub fn transition(mut self, guard: impl Fn(&C) -> bool + 'static, target: S) -> Self {
+        self.transitions.push((Box::new(guard), target));
+        self
+    }
+}
+
+/// Hierarchical finite state machine over a context *C*.
+///
+/// Transitions of outer states are checked first, so a parent can leave
+/// whatever child is active. Entering a parent enters its initial child.
+/// Keep the data the hooks change in a separate struct from the machine:
+/// ```ignore
+/// struct Goblin {
+///     ai: StateMachine<Mode, GoblinData>,
+///     data: GoblinData,
+/// }
+/// self.ai.update(&mut self.data);
+/// ```
+pub struct StateMachine<S, C> {
+    states: HashMap<S, State<S, C>>,
+    initial: S,
+    /// active states from the outermost to the innermost
+    active: Vec<S>,
+    /// ticks since the innermost state was entered
+    ticks: u32,
+}
+
+impl<S, C> StateMachine<S, C>
+where
+    S: Copy + Eq + Hash + Debug,
+{
+    /// Machine starting in *initial* on the first update.
+    pub fn new(initial: S) -> Self {
+        Self {
+            states: HashMap::new(),
+            initial,
+            active: Vec::new(),
+            ticks: 0,
+        }
+    }
+
+    /// Add or replace the state *id*.
+    /// Fails with `Error::HierarchyCycle` if *id* would become its own ancestor.
+    pub fn state(mut self, id: S, state: State<S, C>) -> Result<Self, Error> {
+        let mut ancestor = state.parent;
+        while let Some(parent) = ancestor {
+            if parent == id {
+                return Err(Error::HierarchyCycle);
+            }
+            ancestor = self.states.get(&parent).and_then(|s| s.parent);
+        }
+        self.states.insert(id, state);
+        Ok(self)
+    }
+
+    /// Innermost active state. It is the initial state before the first update.
+    pub fn current(&self) -> S {
+        self.active.last().copied().unwrap_or(self.initial)
+    }
+
+    /// Whether *id* or one of its children is active.
+    pub fn is_in(&self, id: S) -> bool {
+        self.active.contains(&id)
+    }
+
+    /// Ticks since the current state was entered.
+    pub fn ticks_in_state(&self) -> u32 {
+        self.ticks
+    }
+
+    /// Animation tag of the innermost active state that has one.
+    pub fn animation(&self) -> Option<&str> {
+        self.active
+            .iter()
+            .rev()
+            .find_map(|id| self.states.get(id)?.animation.as_deref())
+    }
+
+    /// Check transitions and run `on_update` of the active states, outermost first.
+    pub fn update(&mut self, context: &mut C) {
+        if self.active.is_empty() {
+            self.transition_to(self.initial, context);
+            return;
+        }
+        self.ticks += 1;
+
+        let target = self.active.iter().find_map(|id| {
+            let state = self.states.get(id)?;
+            state
+                .transitions
+                .iter()
+                .find(|(guard, _)| guard(context))
+                .map(|(_, target)| *target)
+        });
+        if let Some(target) = target {
+            self.transition_to(target, context);
+            return;
+        }
+
+        for idx in 0..self.active.len() {
+            let id = self.active[idx];
+            let Some(hook) = self
+                .states
+                .get_mut(&id)
+                .and_then(|state| state.on_update.as_mut())
+            else {
+                continue;
+            };
+            if let Some(target) = hook(context) {
+                self.transition_to(target, context);
+                return;
+            }
+        }
+    }
+
+    /// Leave the active states up to the common parent of *target* and enter it.
+    /// A transition to the current state exits and enters it again.
+    pub fn transition_to(&mut self, target: S, context: &mut C) {
+        let mut path = vec![target];
+        // `state` rejects parent loops, so this ends
+        while let Some(parent) = self.states.get(path.last().unwrap()).and_then(|s| s.parent) {
+            path.push(parent);
+        }
+        path.reverse();
+        while let Some(child) = self
+            .states
+            .get(path.last().unwrap())
+            .and_then(|s| s.initial_child)
+        {
+            if path.contains(&child) {
+                break;
+            }
+            path.push(child);
+        }
+
+        // the target itself is always re-entered
+        let target_depth = path.iter().position(|id| *id == target).unwrap();
+        let common = self
+            .active
+            .iter()
+            .zip(path.iter())
+            .take(target_depth)
+            .take_while(|(active, next)| active == next)
+            .count();
+
+        while self.active.len() > common {
+            let id = self.active.pop().unwrap();
+            if let Some(hook) = self.states.get_mut(&id).and_then(|s| s.on_exit.as_mut()) {
+                hook(context);
+            }
+        }
+        for id in path.into_iter().skip(common) {
+            self.active.push(id);
+            if let Some(hook) = self.states.get_mut(&id).and_then(|s| s.on_enter.as_mut()) {
+                hook(context);
+            }
+        }
+        self.ticks = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    enum Mode {
+        Alive,
+        Idle,
+        Chase,
+    }
+
+    #[test]
+    fn parent_loop_is_rejected() {
+        let machine = StateMachine::<Mode, ()>::new(Mode::Idle)
+            .state(Mode::Alive, State::new().parent(Mode::Chase))
+            .and_then(|machine| machine.state(Mode::Idle, State::new().parent(Mode::Alive)));
+        let Ok(machine) = machine else {
+            panic!("no loop yet");
+        };
+        let looped = machine.state(Mode::Chase, State::new().parent(Mode::Idle));
+        assert!(matches!(looped, Err(Error::HierarchyCycle)));
+    }
+
+    #[test]
+    fn entering_a_child_enters_its_parent() {
+        let mut machine = StateMachine::<Mode, Vec<Mode>>::new(Mode::Idle)
+            .state(
+                Mode::Alive,
+                State::new().on_enter(|entered: &mut Vec<Mode>| entered.push(Mode::Alive)),
+            )
+            .and_then(|machine| {
+                machine.state(
+                    Mode::Idle,
+                    State::new()
+                        .parent(Mode::Alive)
+                        .on_enter(|entered: &mut Vec<Mode>| entered.push(Mode::Idle)),
+                )
+            })
+            .unwrap();
+
+        let mut entered = Vec::new();
+        machine.update(&mut entered);
+        assert_eq!(entered, vec![Mode::Alive, Mode::Idle]);
+        assert!(machine.is_in(Mode::Alive));
+    }
+}
@@ -1,41 +1,207 @@
 use std::cell::RefCell;
+use std::ops::RangeInclusive;
 use std::rc::Rc;
 
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::game::ai::fsm::{State, StateMachine};
+use crate::game::lifecycle::Commands;
+use crate::game::scene::SceneInfo;
 use crate::render::texture::Texture;
+use crate::render::Renderer;
 use crate::types::*;
 
 pub type Entity = Rc<RefCell<dyn EntityTrait>>;
+
+/// Identifies an entity in a scene. Ids are not reused.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EntityId(pub(crate) u64);
+
+/// address of the entity, used as a key in scene tables
+pub(crate) fn entity_key(entity: &Entity) -> usize {
+    Rc::as_ptr(entity) as *const () as usize
+}
+
+/// Render layers, drawn from `Background` to `UI`.
+/// UI elements of the scene are drawn at the beginning of `UI` layer.
+#[derive(
+    Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub enum RenderLayer {
+    Background,
+    #[default]
+    World,
+    Foreground,
+    UI,
+}
+
+impl RenderLayer {
+    pub const COUNT: usize = 4;
+    pub const ALL: [RenderLayer; RenderLayer::COUNT] = [
+        RenderLayer::Background,
+        RenderLayer::World,
+        RenderLayer::Foreground,
+        RenderLayer::UI,
+    ];
+}
+
 pub struct EntityDrawInfo {
     pub world_rect: Rect,
     pub texture: Texture,
     pub texture_idx: usize,
 }
 
+/// Saved state of an entity. *kind* selects the loader registered to `SaveManager`.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct EntityState {
+    pub kind: String,
+    pub data: Value,
+}
+
 pub trait EntityTrait {
     fn pos(&self) -> Vec2;
     fn set_pos(&mut self, new_pos: Vec2);
 
     fn update(&mut self);
     fn get_draw_info(&self) -> EntityDrawInfo;
+
+    /// Layer to draw the entity. Read every frame, so it can be changed at runtime.
+    fn layer(&self) -> RenderLayer {
+        RenderLayer::World
+    }
+
+    /// Draw order within the layer. Higher z-index is drawn on top.
+    fn z_index(&self) -> i32 {
+        0
+    }
+
+    /// World space bounds used to cull the entity before `get_draw_info` is called.
+    /// `None` means the entity is culled by the rect of `get_draw_info`.
+    fn bounds(&self) -> Option<Rect> {
+        None
+    }
+
+    /// Collision area in world space, shown by the debug overlay.
+    /// Keep it inside `bounds`, collisions are looked up in the spatial index.
+    fn collider(&self) -> Option<Rect> {
+        None
+    }
+
+    /// Times the animation reached its last frame and started over.
+    /// `TaskContext::wait_animation` resumes when it grows.
+    fn animation_loops(&self) -> u64 {
+        0
+    }
+
+    /// Draw additional primitives.
+    /// It is drawn on top of every sprite in the same layer.
+    fn draw(&self, _renderer: &mut Renderer, _scene_info: &SceneInfo) {}
+
+    /// Called when the entity is added to a scene.
+    /// Keep a clone of *commands* to spawn or despawn entities later.
+    fn on_spawn(&mut self, _id: EntityId, _commands: &Commands) {}
+
+    /// Called when the entity is removed from the scene.
+    fn on_despawn(&mut self) {}
+
+    /// State written to save files. `None` means the entity is not saved.
+    fn save_state(&self) -> Option<EntityState> {
+        None
+    }
+}
+
+/// Saved fields of `CharacterEntity`
+#[derive(Serialize, Deserialize)]
+struct CharacterState {
+    position: Vec2,
+    anim_idx: usize,
+    layer: RenderLayer,
+    z_index: i32,
+}
+
+/// Animation states of `CharacterEntity`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Motion {
+    Idle,
+    Walk,
 }
 
 /// Human entity for test
 pub struct CharacterEntity {
     texture: Texture,
     position: Vec2,
+    /// position at the last update, to tell if it is walking
+    last_position: Vec2,
+    /// driven by the movement since the last update
+    motion: StateMachine<Motion, Vec2>,
     anim_idx: usize,
     anim_delay: usize,
+    anim_loops: u64,
+    layer: RenderLayer,
+    z_index: i32,
 }
 
 impl CharacterEntity {
     pub fn new(texture: Texture, pos: Vec2) -> Rc<RefCell<Self>> {
+        let is_moving = |moved: &Vec2| moved.x != 0 || moved.y != 0;
+        let motion = StateMachine::new(Motion::Idle)
+            .state(
+                Motion::Idle,
+                State::new()
+                    .animation("idle")
+                    .transition(is_moving, Motion::Walk),
+            )
+            .and_then(|motion| {
+                motion.state(
+                    Motion::Walk,
+                    State::new()
+                        .animation("walk")
+                        .transition(move |moved| !is_moving(moved), Motion::Idle),
+                )
+            })
+            .expect("motion states have no parents");
         Rc::new(RefCell::new(Self {
             texture,
             position: pos,
+            last_position: pos,
+            motion,
             anim_idx: 0,
             anim_delay: 20,
+            anim_loops: 0,
+            layer: RenderLayer::World,
+            z_index: 0,
         }))
     }
+
+    /// Recreate an entity from `save_state` data.
+    pub fn from_state(texture: Texture, data: &Value) -> Option<Rc<RefCell<Self>>> {
+        let state: CharacterState = serde_json::from_value(data.clone()).ok()?;
+        let entity = Self::new(texture, state.position);
+        {
+            let mut entity_mut = entity.borrow_mut();
+            entity_mut.anim_idx = state.anim_idx;
+            entity_mut.layer = state.layer;
+            entity_mut.z_index = state.z_index;
+        }
+        Some(entity)
+    }
+
+    pub fn set_layer(&mut self, layer: RenderLayer) {
+        self.layer = layer;
+    }
+
+    pub fn set_z_index(&mut self, z_index: i32) {
+        self.z_index = z_index;
+    }
+
+    /// Frames of the current animation tag, or every frame if the texture has no such tag.
+    fn frames(&self) -> RangeInclusive<usize> {
+        self.motion
+            .animation()
+            .and_then(|tag| self.texture.frame_tag(tag))
+            .unwrap_or(0..=self.texture.len().saturating_sub(1))
+    }
 }
 
 impl EntityTrait for CharacterEntity {
@@ -47,13 +213,45 @@ impl EntityTrait for CharacterEntity {
     }
 
     fn update(&mut self) {
+        let mut moved = self.position - self.last_position;
+        self.last_position = self.position;
+        self.motion.update(&mut moved);
+
+        let frames = self.frames();
+        if !frames.contains(&self.anim_idx) {
+            // the animation changed
+            self.anim_idx = *frames.start();
+            self.anim_delay = 20;
+        }
         self.anim_delay -= 1;
         if self.anim_delay == 0 {
             // next frame
             self.anim_delay = 20;
-            self.anim_idx = (self.anim_idx + 1) % self.texture.len();
+            self.anim_idx = if self.anim_idx >= *frames.end() {
+                self.anim_loops += 1;
+                *frames.start()
+            } else {
+                self.anim_idx + 1
+            };
         }
     }
+    fn layer(&self) -> RenderLayer {
+        self.layer
+    }
+    fn z_index(&self) -> i32 {
+        self.z_index
+    }
+    fn animation_loops(&self) -> u64 {
+        self.anim_loops
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        Some(Rect::from_center_size(
+            self.position,
+            Vec2 { x: 200, y: 200 },
+        ))
+    }
+
     fn get_draw_info(&self) -> EntityDrawInfo {
         EntityDrawInfo {
             world_rect: Rect::from_center_size(self.position, Vec2 { x: 200, y: 200 }),
@@ -61,4 +259,17 @@ impl EntityTrait for CharacterEntity {
             texture_idx: self.anim_idx,
         }
     }
+
+    fn save_state(&self) -> Option<EntityState> {
+        let state = CharacterState {
+            position: self.position,
+            anim_idx: self.anim_idx,
+            layer: self.layer,
+            z_index: self.z_index,
+        };
+        Some(EntityState {
+            kind: String::from("character"),
+            data: serde_json::to_value(state).ok()?,
+        })
+    }
 }
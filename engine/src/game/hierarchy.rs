@@ -0,0 +1,193 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use crate::error::Error;
+use crate::game::entity::{entity_key, Entity, EntityTrait};
+use crate::game::scene::Scene;
+use crate::types::*;
+
+struct Node {
+    entity: Entity,
+    /// weak, so a link never keeps a removed parent alive
+    parent: Option<Weak<RefCell<dyn EntityTrait>>>,
+    /// offset from the parent, or rotation and scale of a root entity
+    local: Transform,
+}
+
+/// Parent-child links between the entities of a scene.
+#[derive(Default)]
+pub(crate) struct Hierarchy {
+    /// keyed by entity address
+    nodes: HashMap<usize, Node>,
+}
+
+impl Hierarchy {
+    fn parent_of(&self, entity: &Entity) -> Option<Entity> {
+        self.nodes
+            .get(&entity_key(entity))?
+            .parent
+            .as_ref()?
+            .upgrade()
+    }
+
+    fn is_descendant(&self, entity: &Entity, ancestor: &Entity) -> bool {
+        let mut current = self.parent_of(entity);
+        while let Some(parent) = current {
+            if Rc::ptr_eq(&parent, ancestor) {
+                return true;
+            }
+            current = self.parent_of(&parent);
+        }
+        false
+    }
+
+    fn world_transform(&self, entity: &Entity) -> Transform {
+        let node = self.nodes.get(&entity_key(entity));
+        let parent = node.and_then(|node| node.parent.as_ref()?.upgrade());
+        match (node, parent) {
+            (Some(node), Some(parent)) => self.world_transform(&parent).compose(node.local),
+            (Some(Node { local, .. }), None) => Transform {
+                x: entity.borrow().pos().x as f32,
+                y: entity.borrow().pos().y as f32,
+                ..*local
+            },
+            (None, _) => Transform::from_position(entity.borrow().pos()),
+        }
+    }
+
+    fn children(&self, entity: &Entity) -> Vec<Entity> {
+        self.nodes
+            .values()
+            .filter(|node| {
+                node.parent
+                    .as_ref()
+                    .and_then(Weak::upgrade)
+                    .is_some_and(|parent| Rc::ptr_eq(&parent, entity))
+            })
+            .map(|node| node.entity.clone())
+            .collect()
+    }
+
+    fn node_mut(&mut self, entity: &Entity) -> &mut Node {
+        self.nodes
+            .entry(entity_key(entity))
+            .or_insert_with(|| Node {
+                entity: entity.clone(),
+                parent: None,
+                local: Transform::IDENTITY,
+            })
+    }
+}
+
+/// Entity hierarchy.
+///
+/// A child is placed by its local transform relative to the parent,
+/// and its position is overwritten at the end of every `Scene::update`.
+/// Rotation and scale move the children; sprites are still drawn unrotated.
+impl Scene {
+    /// Attach *child* to *parent*, or detach it with `None`. The world position is kept.
+    /// Fails if *parent* is *child* or one of its descendants.
+    pub fn set_parent(&mut self, child: &Entity, parent: Option<&Entity>) -> Result<(), Error> {
+        if let Some(parent) = parent {
+            if Rc::ptr_eq(child, parent) || self.hierarchy.is_descendant(parent, child) {
+                return Err(Error::HierarchyCycle);
+            }
+        }
+
+        let world = self.hierarchy.world_transform(child);
+        let local = match parent {
+            Some(parent) => self
+                .hierarchy
+                .world_transform(parent)
+                .inverse()
+                .compose(world),
+            None => Transform {
+                x: 0.0,
+                y: 0.0,
+                ..world
+            },
+        };
+        let node = self.hierarchy.node_mut(child);
+        node.parent = parent.map(Rc::downgrade);
+        node.local = local;
+        child.borrow_mut().set_pos(world.position());
+        Ok(())
+    }
+
+    pub fn parent(&self, entity: &Entity) -> Option<Entity> {
+        self.hierarchy.parent_of(entity)
+    }
+
+    pub fn children(&self, entity: &Entity) -> Vec<Entity> {
+        self.hierarchy.children(entity)
+    }
+
+    /// Offset, rotation and scale relative to the parent.
+    /// For an entity without a parent, the position is `pos()`.
+    pub fn local_transform(&self, entity: &Entity) -> Transform {
+        match self.hierarchy.nodes.get(&entity_key(entity)) {
+            Some(node) if node.parent.is_some() => node.local,
+            _ => self.hierarchy.world_transform(entity),
+        }
+    }
+
+    pub fn set_local_transform(&mut self, entity: &Entity, transform: Transform) {
+        let node = self.hierarchy.node_mut(entity);
+        if node.parent.is_some() {
+            node.local = transform;
+        } else {
+            node.local = Transform {
+                x: 0.0,
+                y: 0.0,
+                ..transform
+            };
+            entity.borrow_mut().set_pos(transform.position());
+        }
+        self.update_hierarchy();
+    }
+
+    /// Transform in world space, composed from every ancestor.
+    pub fn world_transform(&self, entity: &Entity) -> Transform {
+        self.hierarchy.world_transform(entity)
+    }
+
+    /// Remove *entity* and all its descendants from the scene.
+    pub fn remove_entity(&mut self, entity: &Entity) {
+        let mut removed = vec![entity.clone()];
+        let mut idx = 0;
+        while idx < removed.len() {
+            let children = self.hierarchy.children(&removed[idx]);
+            removed.extend(children);
+            idx += 1;
+        }
+
+        self.entity_list
+            .retain(|candidate| !removed.iter().any(|entity| Rc::ptr_eq(candidate, entity)));
+        for entity in removed.iter() {
+            self.hierarchy.nodes.remove(&entity_key(entity));
+            self.registry.remove(entity);
+            self.spatial.remove(entity);
+            entity.borrow_mut().on_despawn();
+        }
+    }
+
+    /// Move every child to the place given by its parent.
+    pub fn update_hierarchy(&mut self) {
+        let positions: Vec<(Entity, Vec2)> = self
+            .hierarchy
+            .nodes
+            .values()
+            .filter(|node| node.parent.is_some())
+            .map(|node| {
+                let world = self.hierarchy.world_transform(&node.entity);
+                (node.entity.clone(), world.position())
+            })
+            .collect();
+        for (entity, position) in positions {
+            if entity.borrow().pos() != position {
+                entity.borrow_mut().set_pos(position);
+            }
+        }
+    }
+}
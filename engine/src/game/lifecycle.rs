@@ -0,0 +1,218 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::game::entity::{entity_key, Entity, EntityId};
+use crate::game::scene::Scene;
+use crate::game::spatial;
+
+enum Command {
+    Spawn {
+        entity: Entity,
+        name: Option<String>,
+        tags: Vec<String>,
+    },
+    Despawn(EntityId),
+}
+
+/// Deferred spawn and despawn requests, applied by the scene between ticks.
+/// Clone it into entities to spawn or despawn from `EntityTrait::update`.
+#[derive(Clone, Default)]
+pub struct Commands {
+    queue: Rc<RefCell<Vec<Command>>>,
+}
+
+impl Commands {
+    pub fn spawn(&self, entity: Entity) {
+        self.spawn_named(entity, None, &[]);
+    }
+
+    /// Spawn *entity* with *name* and *tags*.
+    pub fn spawn_named(&self, entity: Entity, name: Option<&str>, tags: &[&str]) {
+        self.queue.borrow_mut().push(Command::Spawn {
+            entity,
+            name: name.map(str::to_string),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        });
+    }
+
+    /// Remove the entity and its children. Unknown ids are ignored.
+    pub fn despawn(&self, id: EntityId) {
+        self.queue.borrow_mut().push(Command::Despawn(id));
+    }
+}
+
+struct Record {
+    id: EntityId,
+    name: Option<String>,
+    tags: HashSet<String>,
+}
+
+/// Ids, names and tags of the entities in a scene.
+#[derive(Default)]
+pub(crate) struct Registry {
+    next_id: u64,
+    by_id: HashMap<EntityId, Entity>,
+    /// keyed by entity address
+    records: HashMap<usize, Record>,
+}
+
+impl Registry {
+    fn record_mut(&mut self, id: EntityId) -> Option<&mut Record> {
+        let entity = self.by_id.get(&id)?;
+        self.records.get_mut(&entity_key(entity))
+    }
+
+    pub(crate) fn remove(&mut self, entity: &Entity) {
+        if let Some(record) = self.records.remove(&entity_key(entity)) {
+            self.by_id.remove(&record.id);
+        }
+    }
+}
+
+/// Entity lifecycle and queries.
+///
+/// Every entity added to the scene gets an `EntityId` that stays the same until it is removed.
+/// `Commands` queued during a tick are applied at the start and the end of `Scene::update`.
+impl Scene {
+    /// Add *entity* now and call its `on_spawn`.
+    /// Adding an entity that is already in the scene returns its id.
+    pub fn add_entity(&mut self, entity: Entity) -> EntityId {
+        if let Some(id) = self.id_of(&entity) {
+            return id;
+        }
+        let id = EntityId(self.registry.next_id);
+        self.registry.next_id += 1;
+        self.registry.by_id.insert(id, entity.clone());
+        self.registry.records.insert(
+            entity_key(&entity),
+            Record {
+                id,
+                name: None,
+                tags: HashSet::new(),
+            },
+        );
+        self.entity_list.push(entity.clone());
+        self.spatial
+            .update(&entity, spatial::entity_bounds(&entity));
+        entity.borrow_mut().on_spawn(id, &self.commands);
+        id
+    }
+
+    /// Command buffer of this scene.
+    pub fn commands(&self) -> Commands {
+        self.commands.clone()
+    }
+
+    /// Apply the queued spawns and despawns.
+    /// Commands queued by `on_spawn` or `on_despawn` are applied on the next call.
+    pub fn apply_commands(&mut self) {
+        let commands = std::mem::take(&mut *self.commands.queue.borrow_mut());
+        for command in commands {
+            match command {
+                Command::Spawn { entity, name, tags } => {
+                    let id = self.add_entity(entity);
+                    if let Some(name) = name {
+                        self.set_name(id, &name);
+                    }
+                    for tag in tags {
+                        self.add_tag(id, &tag);
+                    }
+                }
+                Command::Despawn(id) => self.despawn(id),
+            }
+        }
+    }
+
+    /// Remove the entity with *id* and its children now.
+    pub fn despawn(&mut self, id: EntityId) {
+        if let Some(entity) = self.entity(id) {
+            self.remove_entity(&entity);
+        }
+    }
+
+    /// Remove every entity.
+    pub fn clear_entities(&mut self) {
+        let entities = std::mem::take(&mut self.entity_list);
+        for entity in entities.iter() {
+            self.registry.remove(entity);
+            entity.borrow_mut().on_despawn();
+        }
+        self.hierarchy = Default::default();
+        self.spatial.clear();
+    }
+
+    pub fn entity(&self, id: EntityId) -> Option<Entity> {
+        self.registry.by_id.get(&id).cloned()
+    }
+
+    pub fn id_of(&self, entity: &Entity) -> Option<EntityId> {
+        self.registry
+            .records
+            .get(&entity_key(entity))
+            .map(|record| record.id)
+    }
+
+    /// Name *id*. Names are not required to be unique.
+    pub fn set_name(&mut self, id: EntityId, name: &str) {
+        if let Some(record) = self.registry.record_mut(id) {
+            record.name = Some(name.to_string());
+        }
+    }
+
+    pub fn name(&self, id: EntityId) -> Option<String> {
+        let entity = self.registry.by_id.get(&id)?;
+        self.registry.records.get(&entity_key(entity))?.name.clone()
+    }
+
+    /// First entity named *name*.
+    pub fn find_by_name(&self, name: &str) -> Option<Entity> {
+        self.entity_list
+            .iter()
+            .find(|entity| {
+                self.registry
+                    .records
+                    .get(&entity_key(entity))
+                    .is_some_and(|record| record.name.as_deref() == Some(name))
+            })
+            .cloned()
+    }
+
+    pub fn add_tag(&mut self, id: EntityId, tag: &str) {
+        if let Some(record) = self.registry.record_mut(id) {
+            record.tags.insert(tag.to_string());
+        }
+    }
+
+    pub fn remove_tag(&mut self, id: EntityId, tag: &str) {
+        if let Some(record) = self.registry.record_mut(id) {
+            record.tags.remove(tag);
+        }
+    }
+
+    pub fn has_tag(&self, entity: &Entity, tag: &str) -> bool {
+        self.registry
+            .records
+            .get(&entity_key(entity))
+            .is_some_and(|record| record.tags.contains(tag))
+    }
+
+    /// Tags of *id* in no particular order.
+    pub fn tags(&self, id: EntityId) -> Vec<String> {
+        self.registry
+            .by_id
+            .get(&id)
+            .and_then(|entity| self.registry.records.get(&entity_key(entity)))
+            .map(|record| record.tags.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Entities with *tag*, in the order they were added.
+    pub fn with_tag(&self, tag: &str) -> Vec<Entity> {
+        self.entity_list
+            .iter()
+            .filter(|entity| self.has_tag(entity, tag))
+            .cloned()
+            .collect()
+    }
+}
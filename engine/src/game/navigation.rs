@@ -0,0 +1,714 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::game::scene::Scene;
+use crate::types::*;
+
+/// cost of a straight step; a diagonal step costs `DIAGONAL_COST`
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+type Cell = (i32, i32);
+
+const STRAIGHT: [Cell; 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const DIAGONAL: [Cell; 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+/// When a path may move diagonally between cells.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Diagonal {
+    Never,
+    /// only if both cells beside the step are walkable, so corners are never cut
+    #[default]
+    IfNoObstacles,
+    /// if at least one cell beside the step is walkable
+    IfAtMostOneObstacle,
+    /// even between two blocked cells
+    Always,
+}
+
+/// Walkable cells of a world region, for pathfinding.
+///
+/// Cell `(0, 0)` is at the left-bottom corner of the region and y goes up, like world space.
+/// Paths are returned as waypoints in world coordinates, at the center of each cell.
+#[derive(Clone, Debug)]
+pub struct NavGrid {
+    /// world space coordinate of the left-bottom corner
+    origin: Vec2,
+    cell_size: i32,
+    width: i32,
+    height: i32,
+    walkable: Vec<bool>,
+    diagonal: Diagonal,
+}
+
+impl NavGrid {
+    /// Grid of *width* x *height* walkable cells.
+    pub fn new(origin: Vec2, cell_size: i32, width: i32, height: i32) -> Self {
+        let (width, height) = (width.max(0), height.max(0));
+        Self {
+            origin,
+            cell_size: cell_size.max(1),
+            width,
+            height,
+            walkable: vec![true; (width * height) as usize],
+            diagonal: Diagonal::default(),
+        }
+    }
+
+    /// Grid from a tilemap layer. *tiles* are row-major with the first row at the top,
+    /// as in tilemap files. *origin* is the left-bottom corner of the map.
+    pub fn from_tiles<F>(
+        origin: Vec2,
+        tile_size: i32,
+        width: i32,
+        tiles: &[u32],
+        is_solid: F,
+    ) -> Self
+    where
+        F: Fn(u32) -> bool,
+    {
+        let height = if width > 0 {
+            tiles.len() as i32 / width
+        } else {
+            0
+        };
+        let mut grid = Self::new(origin, tile_size, width, height);
+        for (idx, tile) in tiles.iter().enumerate().take((width * height) as usize) {
+            let x = idx as i32 % width;
+            let y = height - 1 - idx as i32 / width;
+            grid.set_walkable((x, y), !is_solid(*tile));
+        }
+        grid
+    }
+
+    /// Grid covering *area*, with cells overlapping a collider blocked.
+    pub fn from_colliders<I>(area: Rect, cell_size: i32, colliders: I) -> Self
+    where
+        I: IntoIterator<Item = Rect>,
+    {
+        let cell_size = cell_size.max(1);
+        let width = (area.w + cell_size - 1) / cell_size;
+        let height = (area.h + cell_size - 1) / cell_size;
+        let mut grid = Self::new(area.point_left_bottom(), cell_size, width, height);
+        for collider in colliders {
+            grid.block_rect(collider);
+        }
+        grid
+    }
+
+    /// Grid covering *area*, blocked by the colliders of the entities in it.
+    /// Only entities with *tag* are obstacles if given, so moving characters don't block themselves.
+    pub fn from_scene(scene: &Scene, area: Rect, cell_size: i32, tag: Option<&str>) -> Self {
+        let colliders = scene
+            .query_rect(area)
+            .into_iter()
+            .filter(|entity| tag.is_none_or(|tag| scene.has_tag(entity, tag)))
+            .filter_map(|entity| entity.borrow().collider());
+        Self::from_colliders(area, cell_size, colliders)
+    }
+
+    pub fn set_diagonal(&mut self, diagonal: Diagonal) {
+        self.diagonal = diagonal;
+    }
+
+    pub fn diagonal(&self) -> Diagonal {
+        self.diagonal
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn cell_size(&self) -> i32 {
+        self.cell_size
+    }
+
+    fn index(&self, (x, y): Cell) -> Option<usize> {
+        if 0 <= x && x < self.width && 0 <= y && y < self.height {
+            Some((y * self.width + x) as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Cells outside the grid are not walkable.
+    pub fn is_walkable(&self, cell: (i32, i32)) -> bool {
+        self.index(cell).is_some_and(|idx| self.walkable[idx])
+    }
+
+    pub fn set_walkable(&mut self, cell: (i32, i32), walkable: bool) {
+        if let Some(idx) = self.index(cell) {
+            self.walkable[idx] = walkable;
+        }
+    }
+
+    /// Block every cell overlapping *rect*.
+    pub fn block_rect(&mut self, rect: Rect) {
+        let top_right = rect.point_right_top() - self.origin;
+        let left_bottom = rect.point_left_bottom() - self.origin;
+        // exclusive on the right and top edge
+        let max_x = (top_right.x - 1)
+            .div_euclid(self.cell_size)
+            .min(self.width - 1);
+        let max_y = (top_right.y - 1)
+            .div_euclid(self.cell_size)
+            .min(self.height - 1);
+        for x in left_bottom.x.div_euclid(self.cell_size).max(0)..=max_x {
+            for y in left_bottom.y.div_euclid(self.cell_size).max(0)..=max_y {
+                self.set_walkable((x, y), false);
+            }
+        }
+    }
+
+    /// Cell containing *point*, even if it is outside the grid.
+    pub fn cell_at(&self, point: Vec2) -> (i32, i32) {
+        let local = point - self.origin;
+        (
+            local.x.div_euclid(self.cell_size),
+            local.y.div_euclid(self.cell_size),
+        )
+    }
+
+    /// World space center of *cell*.
+    pub fn cell_center(&self, (x, y): (i32, i32)) -> Vec2 {
+        self.origin
+            + Vec2 {
+                x: x * self.cell_size + self.cell_size / 2,
+                y: y * self.cell_size + self.cell_size / 2,
+            }
+    }
+
+    /// Whether the step from *cell* by *step* is allowed by the diagonal rule.
+    fn can_step(&self, (x, y): Cell, (dx, dy): Cell) -> bool {
+        if !self.is_walkable((x + dx, y + dy)) {
+            return false;
+        }
+        if dx == 0 || dy == 0 {
+            return true;
+        }
+        let beside = [self.is_walkable((x + dx, y)), self.is_walkable((x, y + dy))];
+        match self.diagonal {
+            Diagonal::Never => false,
+            Diagonal::IfNoObstacles => beside[0] && beside[1],
+            Diagonal::IfAtMostOneObstacle => beside[0] || beside[1],
+            Diagonal::Always => true,
+        }
+    }
+
+    fn neighbors(&self, cell: Cell) -> impl Iterator<Item = (Cell, u32)> + '_ {
+        STRAIGHT
+            .iter()
+            .map(|step| (*step, STRAIGHT_COST))
+            .chain(DIAGONAL.iter().map(|step| (*step, DIAGONAL_COST)))
+            .filter(move |(step, _)| self.can_step(cell, *step))
+            .map(move |((dx, dy), cost)| ((cell.0 + dx, cell.1 + dy), cost))
+    }
+
+    /// estimated cost from *from* to *to*
+    fn heuristic(&self, (x0, y0): Cell, (x1, y1): Cell) -> u32 {
+        let (dx, dy) = ((x1 - x0).unsigned_abs(), (y1 - y0).unsigned_abs());
+        if self.diagonal == Diagonal::Never {
+            STRAIGHT_COST * (dx + dy)
+        } else {
+            STRAIGHT_COST * dx.max(dy) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dy)
+        }
+    }
+
+    /// Shortest path from *from* to *to* by A*.
+    /// Waypoints start at the cell after the one of *from* and end at the center of the cell of *to*.
+    /// `None` if either end is blocked or there is no path.
+    pub fn find_path(&self, from: Vec2, to: Vec2) -> Option<Vec<Vec2>> {
+        let cells = self.search(self.cell_at(from), self.cell_at(to), false)?;
+        Some(self.to_waypoints(&cells))
+    }
+
+    /// Same as `find_path`, by jump point search. It expands far fewer cells on large open grids.
+    /// Only `Diagonal::IfNoObstacles` is supported; other rules fall back to A*.
+    pub fn find_path_jps(&self, from: Vec2, to: Vec2) -> Option<Vec<Vec2>> {
+        let jps = self.diagonal == Diagonal::IfNoObstacles;
+        let cells = self.search(self.cell_at(from), self.cell_at(to), jps)?;
+        Some(self.to_waypoints(&cells))
+    }
+
+    /// A* from *start* to *goal*. With *jps*, successors are jump points instead of neighbors.
+    /// Returns every cell of the path including both ends.
+    fn search(&self, start: Cell, goal: Cell, jps: bool) -> Option<Vec<Cell>> {
+        let start_idx = self.index(start)?;
+        let goal_idx = self.index(goal)?;
+        if !self.walkable[start_idx] || !self.walkable[goal_idx] {
+            return None;
+        }
+
+        let mut cost = vec![u32::MAX; self.walkable.len()];
+        let mut parent: Vec<Option<Cell>> = vec![None; self.walkable.len()];
+        let mut open = BinaryHeap::new();
+        cost[start_idx] = 0;
+        // ties are broken toward lower remaining estimate, which reaches the goal sooner
+        open.push(Reverse((
+            self.heuristic(start, goal),
+            self.heuristic(start, goal),
+            start,
+        )));
+
+        while let Some(Reverse((total, estimate, cell))) = open.pop() {
+            if cell == goal {
+                return Some(self.trace(parent, goal));
+            }
+            let cell_cost = cost[self.index(cell).unwrap()];
+            if total - estimate > cell_cost {
+                // already expanded with a lower cost
+                continue;
+            }
+            let successors: Vec<(Cell, u32)> = if jps {
+                self.jump_successors(cell, parent[self.index(cell).unwrap()], goal)
+            } else {
+                self.neighbors(cell).collect()
+            };
+            for (next, step_cost) in successors {
+                let next_idx = self.index(next).unwrap();
+                let next_cost = cell_cost + step_cost;
+                if next_cost < cost[next_idx] {
+                    cost[next_idx] = next_cost;
+                    parent[next_idx] = Some(cell);
+                    let estimate = self.heuristic(next, goal);
+                    open.push(Reverse((next_cost + estimate, estimate, next)));
+                }
+            }
+        }
+        None
+    }
+
+    /// Cells from the start to *goal*, filling the straight runs between jump points.
+    fn trace(&self, parent: Vec<Option<Cell>>, goal: Cell) -> Vec<Cell> {
+        let mut cells = vec![goal];
+        let mut current = goal;
+        while let Some(previous) = parent[self.index(current).unwrap()] {
+            let step = (
+                (previous.0 - current.0).signum(),
+                (previous.1 - current.1).signum(),
+            );
+            while current != previous {
+                current = (current.0 + step.0, current.1 + step.1);
+                cells.push(current);
+            }
+        }
+        cells.reverse();
+        cells
+    }
+
+    fn to_waypoints(&self, cells: &[Cell]) -> Vec<Vec2> {
+        cells
+            .iter()
+            .skip(1)
+            .map(|cell| self.cell_center(*cell))
+            .collect()
+    }
+
+    /// Jump points reachable from *cell*, with their cost.
+    /// Neighbors are pruned by the direction from *parent*.
+    fn jump_successors(&self, cell: Cell, parent: Option<Cell>, goal: Cell) -> Vec<(Cell, u32)> {
+        let (x, y) = cell;
+        let directions: Vec<Cell> = match parent {
+            None => self
+                .neighbors(cell)
+                .map(|(next, _)| (next.0 - x, next.1 - y))
+                .collect(),
+            Some(parent) => {
+                let (dx, dy) = ((x - parent.0).signum(), (y - parent.1).signum());
+                let mut directions = Vec::new();
+                if dx != 0 && dy != 0 {
+                    if self.is_walkable((x, y + dy)) {
+                        directions.push((0, dy));
+                    }
+                    if self.is_walkable((x + dx, y)) {
+                        directions.push((dx, 0));
+                    }
+                    if self.can_step(cell, (dx, dy)) {
+                        directions.push((dx, dy));
+                    }
+                } else {
+                    // sideways of a straight move, (1, 0) becomes (0, 1) and (0, -1)
+                    let (side_x, side_y) = (dy, dx);
+                    let ahead = self.is_walkable((x + dx, y + dy));
+                    for side in [1, -1] {
+                        let (sx, sy) = (side_x * side, side_y * side);
+                        if self.is_walkable((x + sx, y + sy)) {
+                            if ahead {
+                                directions.push((dx + sx, dy + sy));
+                            }
+                            directions.push((sx, sy));
+                        }
+                    }
+                    if ahead {
+                        directions.push((dx, dy));
+                    }
+                }
+                directions
+            }
+        };
+
+        directions
+            .into_iter()
+            .filter_map(|(dx, dy)| {
+                let jump_point = self.jump((x + dx, y + dy), (dx, dy), goal)?;
+                let steps = (jump_point.0 - x)
+                    .unsigned_abs()
+                    .max((jump_point.1 - y).unsigned_abs());
+                let step_cost = if dx != 0 && dy != 0 {
+                    DIAGONAL_COST
+                } else {
+                    STRAIGHT_COST
+                };
+                Some((jump_point, steps * step_cost))
+            })
+            .collect()
+    }
+
+    /// Walk from *cell* in *direction* until a jump point, for diagonal moves without corner cutting.
+    fn jump(&self, mut cell: Cell, (dx, dy): Cell, goal: Cell) -> Option<Cell> {
+        loop {
+            let (x, y) = cell;
+            if !self.is_walkable(cell) {
+                return None;
+            }
+            if cell == goal {
+                return Some(cell);
+            }
+            if dx != 0 && dy != 0 {
+                if self.jump((x + dx, y), (dx, 0), goal).is_some()
+                    || self.jump((x, y + dy), (0, dy), goal).is_some()
+                {
+                    return Some(cell);
+                }
+                if !self.is_walkable((x + dx, y)) || !self.is_walkable((x, y + dy)) {
+                    return None;
+                }
+            } else {
+                // a side cell that was blocked behind opens up
+                let (side_x, side_y) = (dy, dx);
+                for side in [1, -1] {
+                    let (sx, sy) = (side_x * side, side_y * side);
+                    if self.is_walkable((x + sx, y + sy))
+                        && !self.is_walkable((x - dx + sx, y - dy + sy))
+                    {
+                        return Some(cell);
+                    }
+                }
+            }
+            cell = (x + dx, y + dy);
+        }
+    }
+
+    /// Whether a straight line from the cell of *from* to the cell of *to* only crosses walkable cells.
+    pub fn line_of_sight(&self, from: Vec2, to: Vec2) -> bool {
+        let (mut x, mut y) = self.cell_at(from);
+        let end = self.cell_at(to);
+        let (dx, dy) = ((end.0 - x).abs(), (end.1 - y).abs());
+        let (step_x, step_y) = ((end.0 - x).signum(), (end.1 - y).signum());
+        // supercover line: visits every cell the segment touches
+        let mut error = dx - dy;
+        let (dx, dy) = (dx * 2, dy * 2);
+        loop {
+            if !self.is_walkable((x, y)) {
+                return false;
+            }
+            if (x, y) == end {
+                return true;
+            }
+            if error > 0 {
+                x += step_x;
+                error -= dy;
+            } else if error < 0 {
+                y += step_y;
+                error += dx;
+            } else {
+                // through a corner, both cells beside it must be open
+                if !self.is_walkable((x + step_x, y)) || !self.is_walkable((x, y + step_y)) {
+                    return false;
+                }
+                x += step_x;
+                y += step_y;
+                error += dx - dy;
+            }
+        }
+    }
+
+    /// Remove waypoints that can be skipped in a straight line.
+    /// *from* is the position the path starts at.
+    pub fn smooth_path(&self, from: Vec2, path: &[Vec2]) -> Vec<Vec2> {
+        let mut smoothed = Vec::new();
+        let mut anchor = from;
+        let mut idx = 0;
+        while idx < path.len() {
+            // farthest waypoint still visible from the anchor
+            let mut farthest = idx;
+            while farthest + 1 < path.len() && self.line_of_sight(anchor, path[farthest + 1]) {
+                farthest += 1;
+            }
+            smoothed.push(path[farthest]);
+            anchor = path[farthest];
+            idx = farthest + 1;
+        }
+        smoothed
+    }
+
+    /// Flow field toward the nearest of *goals*. Blocked goals are ignored.
+    pub fn flow_field(&self, goals: &[Vec2]) -> FlowField {
+        let mut cost = vec![u32::MAX; self.walkable.len()];
+        let mut open = BinaryHeap::new();
+        for goal in goals {
+            let cell = self.cell_at(*goal);
+            if let Some(idx) = self.index(cell).filter(|idx| self.walkable[*idx]) {
+                cost[idx] = 0;
+                open.push(Reverse((0, cell)));
+            }
+        }
+
+        // Dijkstra outward from the goals
+        while let Some(Reverse((cell_cost, cell))) = open.pop() {
+            if cell_cost > cost[self.index(cell).unwrap()] {
+                continue;
+            }
+            for (next, step_cost) in self.neighbors(cell) {
+                let next_idx = self.index(next).unwrap();
+                if cell_cost + step_cost < cost[next_idx] {
+                    cost[next_idx] = cell_cost + step_cost;
+                    open.push(Reverse((cell_cost + step_cost, next)));
+                }
+            }
+        }
+
+        let mut directions = vec![None; self.walkable.len()];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let idx = self.index((x, y)).unwrap();
+                if cost[idx] == 0 || cost[idx] == u32::MAX {
+                    continue;
+                }
+                directions[idx] = self
+                    .neighbors((x, y))
+                    .min_by_key(|(next, step_cost)| {
+                        cost[self.index(*next).unwrap()].saturating_add(*step_cost)
+                    })
+                    .map(|(next, _)| (next.0 - x, next.1 - y));
+            }
+        }
+
+        FlowField {
+            grid: self.clone(),
+            cost,
+            directions,
+        }
+    }
+}
+
+/// Directions toward the goals from every cell of a `NavGrid`.
+/// Build it once and let any number of entities follow it.
+#[derive(Clone, Debug)]
+pub struct FlowField {
+    grid: NavGrid,
+    cost: Vec<u32>,
+    /// step toward the goal, `None` at a goal and where it can't be reached
+    directions: Vec<Option<Cell>>,
+}
+
+impl FlowField {
+    /// Unit direction to move from *point*, with y going up.
+    /// `None` at a goal, on blocked cells and where no goal can be reached.
+    pub fn direction(&self, point: Vec2) -> Option<(f32, f32)> {
+        let idx = self.grid.index(self.grid.cell_at(point))?;
+        let (dx, dy) = self.directions[idx]?;
+        let length = ((dx * dx + dy * dy) as f32).sqrt();
+        Some((dx as f32 / length, dy as f32 / length))
+    }
+
+    /// Center of the next cell to move to from *point*.
+    pub fn next_waypoint(&self, point: Vec2) -> Option<Vec2> {
+        let cell = self.grid.cell_at(point);
+        let (dx, dy) = self.directions[self.grid.index(cell)?]?;
+        Some(self.grid.cell_center((cell.0 + dx, cell.1 + dy)))
+    }
+
+    /// Path cost from *point* to the nearest goal, in cells. `None` if it can't be reached.
+    pub fn distance(&self, point: Vec2) -> Option<f32> {
+        let cost = self.cost[self.grid.index(self.grid.cell_at(point))?];
+        (cost != u32::MAX).then(|| cost as f32 / STRAIGHT_COST as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Rng;
+
+    const CELL: i32 = 10;
+
+    /// Grid from rows drawn top first, `#` for blocked cells.
+    fn grid(rows: &[&str]) -> NavGrid {
+        let height = rows.len() as i32;
+        let width = rows[0].len() as i32;
+        let mut grid = NavGrid::new(Vec2 { x: 0, y: 0 }, CELL, width, height);
+        for (row, line) in rows.iter().enumerate() {
+            for (x, tile) in line.chars().enumerate() {
+                if tile == '#' {
+                    grid.set_walkable((x as i32, height - 1 - row as i32), false);
+                }
+            }
+        }
+        grid
+    }
+
+    fn random_grid(rng: &mut Rng, size: i32) -> NavGrid {
+        let mut grid = NavGrid::new(Vec2 { x: 0, y: 0 }, CELL, size, size);
+        for x in 0..size {
+            for y in 0..size {
+                if rng.chance(0.3) {
+                    grid.set_walkable((x, y), false);
+                }
+            }
+        }
+        grid
+    }
+
+    fn center(x: i32, y: i32) -> Vec2 {
+        Vec2 {
+            x: x * CELL + CELL / 2,
+            y: y * CELL + CELL / 2,
+        }
+    }
+
+    /// Cost of walking *path* from *from*, checking that every step goes to an adjacent cell.
+    fn path_cost(grid: &NavGrid, from: Vec2, path: &[Vec2]) -> u32 {
+        let mut cell = grid.cell_at(from);
+        let mut cost = 0;
+        for waypoint in path {
+            let next = grid.cell_at(*waypoint);
+            let (dx, dy) = ((next.0 - cell.0).abs(), (next.1 - cell.1).abs());
+            assert!(
+                dx <= 1 && dy <= 1 && (dx, dy) != (0, 0),
+                "{cell:?} to {next:?}"
+            );
+            cost += if dx + dy == 2 {
+                DIAGONAL_COST
+            } else {
+                STRAIGHT_COST
+            };
+            cell = next;
+        }
+        cost
+    }
+
+    fn assert_no_corner_cut(grid: &NavGrid, from: Vec2, path: &[Vec2]) {
+        let mut cell = grid.cell_at(from);
+        for waypoint in path {
+            let next = grid.cell_at(*waypoint);
+            assert!(grid.is_walkable(next));
+            if next.0 != cell.0 && next.1 != cell.1 {
+                assert!(grid.is_walkable((next.0, cell.1)), "{cell:?} to {next:?}");
+                assert!(grid.is_walkable((cell.0, next.1)), "{cell:?} to {next:?}");
+            }
+            cell = next;
+        }
+    }
+
+    #[test]
+    fn jps_matches_astar_cost() {
+        let grids = [
+            grid(&[
+                "..........",
+                "..#######.",
+                "........#.",
+                "######..#.",
+                "..........",
+            ]),
+            grid(&[
+                "....#.....",
+                "....#.##..",
+                "..#.#..#..",
+                "..#....#..",
+                "..#.####..",
+            ]),
+            grid(&["..........", "..........", "..........", ".........."]),
+        ];
+        for grid in grids.iter() {
+            let (from, to) = (center(0, 0), center(grid.width() - 1, grid.height() - 1));
+            let astar = grid.find_path(from, to).unwrap();
+            let jps = grid.find_path_jps(from, to).unwrap();
+            assert_eq!(path_cost(grid, from, &astar), path_cost(grid, from, &jps));
+            assert_eq!(jps.last(), Some(&to));
+        }
+
+        let mut rng = Rng::new(7);
+        for _ in 0..100 {
+            let grid = random_grid(&mut rng, 16);
+            let from = center(rng.range_i32(0, 16), rng.range_i32(0, 16));
+            let to = center(rng.range_i32(0, 16), rng.range_i32(0, 16));
+            let astar = grid.find_path(from, to);
+            let jps = grid.find_path_jps(from, to);
+            assert_eq!(astar.is_some(), jps.is_some());
+            if let (Some(astar), Some(jps)) = (astar, jps) {
+                assert_eq!(path_cost(&grid, from, &astar), path_cost(&grid, from, &jps));
+            }
+        }
+    }
+
+    #[test]
+    fn no_corner_cutting() {
+        let grid = grid(&["...", ".#.", "..."]);
+        let (from, to) = (center(0, 0), center(2, 2));
+        for path in [grid.find_path(from, to), grid.find_path_jps(from, to)] {
+            let path = path.unwrap();
+            assert_no_corner_cut(&grid, from, &path);
+            assert_eq!(path_cost(&grid, from, &path), 4 * STRAIGHT_COST);
+        }
+
+        let mut rng = Rng::new(11);
+        for _ in 0..100 {
+            let grid = random_grid(&mut rng, 12);
+            let from = center(rng.range_i32(0, 12), rng.range_i32(0, 12));
+            let to = center(rng.range_i32(0, 12), rng.range_i32(0, 12));
+            for path in [grid.find_path(from, to), grid.find_path_jps(from, to)]
+                .into_iter()
+                .flatten()
+            {
+                assert_no_corner_cut(&grid, from, &path);
+            }
+        }
+    }
+
+    #[test]
+    fn blocked_goal_has_no_path() {
+        let blocked = grid(&["...", "..#", "..."]);
+        let enclosed = grid(&[".###", ".#.#", ".###"]);
+        let from = center(0, 0);
+        assert_eq!(blocked.find_path(from, center(2, 1)), None);
+        assert_eq!(blocked.find_path_jps(from, center(2, 1)), None);
+        assert_eq!(enclosed.find_path(from, center(2, 1)), None);
+        assert_eq!(enclosed.find_path_jps(from, center(2, 1)), None);
+    }
+
+    #[test]
+    fn smoothed_path_keeps_line_of_sight() {
+        let mut rng = Rng::new(3);
+        for _ in 0..100 {
+            let grid = random_grid(&mut rng, 16);
+            let from = center(rng.range_i32(0, 16), rng.range_i32(0, 16));
+            let to = center(rng.range_i32(0, 16), rng.range_i32(0, 16));
+            let Some(path) = grid.find_path(from, to) else {
+                continue;
+            };
+            let smoothed = grid.smooth_path(from, &path);
+            assert!(smoothed.len() <= path.len());
+            assert_eq!(smoothed.last(), path.last());
+            let mut anchor = from;
+            for waypoint in smoothed {
+                assert!(grid.line_of_sight(anchor, waypoint));
+                anchor = waypoint;
+            }
+        }
+    }
+}
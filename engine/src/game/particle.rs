@@ -0,0 +1,268 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::game::entity::{Entity, RenderLayer};
+use crate::game::scene::Camera;
+use crate::random::Rng;
+use crate::render::texture::Texture;
+use crate::render::Renderer;
+use crate::types::*;
+
+pub type ParticleEmitterHandle = Rc<RefCell<ParticleEmitter>>;
+
+/// Region new particles are spawned in, relative to the emitter.
+#[derive(Clone, Copy, Debug)]
+pub enum EmissionShape {
+    Point,
+    Circle { radius: f32 },
+    Rect { size: Vec2 },
+}
+
+/// Coordinate space particles live in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParticleSpace {
+    /// particles stay where they are emitted.
+    World,
+    /// particles move together with the emitter.
+    Local,
+}
+
+/// Settings of a particle emitter.
+/// Time is measured in ticks, that is, calls of `Scene::update`.
+#[derive(Clone)]
+pub struct EmitterConfig {
+    pub texture: Texture,
+    /// texture frames played over the lifetime. empty means frame 0.
+    pub frames: Vec<usize>,
+
+    pub shape: EmissionShape,
+    pub space: ParticleSpace,
+    /// particles emitted per tick. fractions are accumulated.
+    pub rate: f32,
+    /// maximum number of alive particles. the pool is allocated once with this size.
+    pub max_particles: usize,
+
+    /// minimum and maximum lifetime, in ticks.
+    pub lifetime: (u32, u32),
+    /// minimum and maximum initial speed, in world units per tick.
+    pub speed: (f32, f32),
+    /// emission direction in degrees, counterclockwise from +x.
+    pub direction: f32,
+    /// emission angle around *direction*, in degrees.
+    pub spread: f32,
+    /// acceleration in world units per tick squared. y-axis goes up.
+    pub gravity: (f32, f32),
+
+    /// color at the start and the end of the lifetime.
+    pub color: (Color, Color),
+    /// size in world units at the start and the end of the lifetime.
+    pub size: (f32, f32),
+}
+
+impl EmitterConfig {
+    pub fn new(texture: Texture) -> Self {
+        Self {
+            texture,
+            frames: Vec::new(),
+            shape: EmissionShape::Point,
+            space: ParticleSpace::World,
+            rate: 1.0,
+            max_particles: 1000,
+            lifetime: (60, 60),
+            speed: (1.0, 2.0),
+            direction: 90.0,
+            spread: 360.0,
+            gravity: (0.0, 0.0),
+            color: (Color::WHITE, Color::rgba(255, 255, 255, 0)),
+            size: (16.0, 16.0),
+        }
+    }
+}
+
+struct Particle {
+    /// position in world space, or relative to the emitter in local space
+    x: f32,
+    y: f32,
+    vx: f32,
+    vy: f32,
+    age: u32,
+    lifetime: u32,
+}
+
+/// Particle emitter owned by the scene.
+/// Add it with `Scene::add_emitter`.
+pub struct ParticleEmitter {
+    pub config: EmitterConfig,
+    pub position: Vec2,
+    pub layer: RenderLayer,
+    /// whether particles are emitted continuously by `rate`.
+    pub emitting: bool,
+
+    attachment: Option<Entity>,
+    particles: Vec<Particle>,
+    emit_accumulator: f32,
+    /// forked from the `particles` stream when added to a scene
+    pub(crate) rng: Rng,
+}
+
+impl ParticleEmitter {
+    pub fn new(config: EmitterConfig, position: Vec2) -> ParticleEmitterHandle {
+        let particles = Vec::with_capacity(config.max_particles);
+        Rc::new(RefCell::new(Self {
+            config,
+            position,
+            layer: RenderLayer::World,
+            emitting: true,
+            attachment: None,
+            particles,
+            emit_accumulator: 0.0,
+            rng: Rng::new(0),
+        }))
+    }
+
+    /// Follow *target*. the emitter is placed at the position of the entity.
+    pub fn attach(&mut self, target: Entity) {
+        self.attachment = Some(target);
+    }
+    pub fn detach(&mut self) {
+        self.attachment = None;
+    }
+
+    /// world space position of the emitter, following the attachment.
+    pub fn origin(&self) -> Vec2 {
+        match &self.attachment {
+            Some(entity) => entity.borrow().pos(),
+            None => self.position,
+        }
+    }
+
+    /// Emit *count* particles at once.
+    /// Particles over `max_particles` are dropped.
+    pub fn burst(&mut self, count: usize) {
+        for _ in 0..count {
+            if self.particles.len() >= self.config.max_particles {
+                break;
+            }
+            self.spawn();
+        }
+    }
+
+    /// number of alive particles
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    pub fn update(&mut self) {
+        let (gravity_x, gravity_y) = self.config.gravity;
+        let mut idx = 0;
+        while idx < self.particles.len() {
+            let particle = &mut self.particles[idx];
+            particle.age += 1;
+            if particle.age >= particle.lifetime {
+                // dead particles are replaced by the last one, so the pool stays dense
+                self.particles.swap_remove(idx);
+                continue;
+            }
+            particle.vx += gravity_x;
+            particle.vy += gravity_y;
+            particle.x += particle.vx;
+            particle.y += particle.vy;
+            idx += 1;
+        }
+
+        if self.emitting {
+            self.emit_accumulator += self.config.rate;
+            let count = self.emit_accumulator as usize;
+            self.emit_accumulator -= count as f32;
+            self.burst(count);
+        }
+    }
+
+    pub(crate) fn render(&self, renderer: &mut Renderer, camera: &Camera) {
+        let origin = match self.config.space {
+            ParticleSpace::World => (0.0, 0.0),
+            ParticleSpace::Local => {
+                let origin = self.origin();
+                (origin.x as f32, origin.y as f32)
+            }
+        };
+        let (start_size, end_size) = self.config.size;
+        let (start_color, end_color) = self.config.color;
+
+        for particle in self.particles.iter() {
+            let t = particle.age as f32 / particle.lifetime as f32;
+            let size = (start_size + (end_size - start_size) * t).round() as i32;
+            let center = Vec2 {
+                x: (origin.0 + particle.x).round() as i32,
+                y: (origin.1 + particle.y).round() as i32,
+            };
+            let world_rect = Rect::from_center_size(center, Vec2 { x: size, y: size });
+
+            let Some(screen_rect) = renderer.clip(camera.transform(world_rect)) else {
+                continue;
+            };
+            let frame = if self.config.frames.is_empty() {
+                0
+            } else {
+                let idx = (t * self.config.frames.len() as f32) as usize;
+                self.config.frames[idx.min(self.config.frames.len() - 1)]
+            };
+            renderer.draw_sprite_tinted(
+                &self.config.texture,
+                screen_rect,
+                frame,
+                start_color.lerp(end_color, t),
+            );
+        }
+    }
+
+    fn spawn(&mut self) {
+        let offset = match self.config.shape {
+            EmissionShape::Point => (0.0, 0.0),
+            EmissionShape::Circle { radius } => {
+                // uniform in the disk
+                let angle = self.rng.range_f32(0.0, std::f32::consts::TAU);
+                let distance = radius * self.rng.range_f32(0.0, 1.0).sqrt();
+                (angle.cos() * distance, angle.sin() * distance)
+            }
+            EmissionShape::Rect { size } => (
+                self.rng
+                    .range_f32(-size.x as f32 / 2.0, size.x as f32 / 2.0),
+                self.rng
+                    .range_f32(-size.y as f32 / 2.0, size.y as f32 / 2.0),
+            ),
+        };
+        let (x, y) = match self.config.space {
+            ParticleSpace::World => {
+                let origin = self.origin();
+                (origin.x as f32 + offset.0, origin.y as f32 + offset.1)
+            }
+            ParticleSpace::Local => offset,
+        };
+
+        let half_spread = self.config.spread / 2.0;
+        let angle =
+            (self.config.direction + self.rng.range_f32(-half_spread, half_spread)).to_radians();
+        let speed = self.rng.range_f32(self.config.speed.0, self.config.speed.1);
+        let lifetime = self
+            .rng
+            .range_i32(
+                self.config.lifetime.0 as i32,
+                self.config.lifetime.1 as i32 + 1,
+            )
+            .max(1) as u32;
+
+        self.particles.push(Particle {
+            x,
+            y,
+            vx: angle.cos() * speed,
+            vy: angle.sin() * speed,
+            age: 0,
+            lifetime,
+        });
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::Error;
+use crate::game::entity::{Entity, EntityState};
+use crate::game::scene::Scene;
+use crate::game::scheduler::TimerState;
+use crate::random::{RandomService, Rng};
+use crate::types::*;
+
+type EntityLoader = Box<dyn Fn(&Value) -> Option<Entity>>;
+type Migration = Box<dyn Fn(u32, &mut Value) -> Result<(), Error>>;
+
+/// Kind of entities saved by `ScriptEntity`. They are restored through the scene's script host.
+pub const SCRIPT_ENTITY_KIND: &str = "script";
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default)]
+pub struct CameraState {
+    pub center: Vec2,
+    pub zoom: f32,
+}
+
+/// Snapshot of a scene written to a save slot.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct SaveData {
+    pub version: u32,
+    pub camera: CameraState,
+    /// scheduler time. tasks and closure timers are not saved.
+    pub time: Duration,
+    /// keyed timers of the scheduler
+    #[serde(default)]
+    pub timers: Vec<TimerState>,
+    pub entities: Vec<SavedEntity>,
+    /// state of the random streams, so the game continues with the same numbers
+    #[serde(default)]
+    pub random: RandomService,
+    /// random streams of the particle emitters, in the order they were added
+    #[serde(default)]
+    pub emitter_streams: Vec<Rng>,
+    /// game specific data, such as inventory or progress
+    #[serde(default)]
+    pub custom: Value,
+}
+
+/// Entity state with its name and tags in the scene.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct SavedEntity {
+    #[serde(flatten)]
+    pub state: EntityState,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// index of the parent in `SaveData::entities`
+    #[serde(default)]
+    pub parent: Option<usize>,
+}
+
+/// Information of an existing save slot.
+#[derive(Clone, Copy, Debug)]
+pub struct SlotInfo {
+    pub slot: u32,
+    pub modified: SystemTime,
+}
+
+/// Saves and loads scenes to numbered slots in a directory.
+///
+/// Entities are saved by `EntityTrait::save_state`,
+/// and recreated by the loader registered for their kind.
+pub struct SaveManager {
+    directory: PathBuf,
+    version: u32,
+    loaders: HashMap<String, EntityLoader>,
+    migration: Option<Migration>,
+}
+
+impl SaveManager {
+    /// Save files are stored in *directory*, stamped with *version*.
+    pub fn new(directory: &Path, version: u32) -> Self {
+        Self {
+            directory: directory.to_path_buf(),
+            version,
+            loaders: HashMap::new(),
+            migration: None,
+        }
+    }
+
+    /// Recreate entities of *kind* with *loader* from their saved data.
+    pub fn register<F>(&mut self, kind: &str, loader: F)
+    where
+        F: Fn(&Value) -> Option<Entity> + 'static,
+    {
+        self.loaders.insert(kind.to_string(), Box::new(loader));
+    }
+
+    /// Called with the version and the raw JSON of a save older than the current version.
+    /// It should upgrade the JSON to the current format.
+    pub fn set_migration<F>(&mut self, migration: F)
+    where
+        F: Fn(u32, &mut Value) -> Result<(), Error> + 'static,
+    {
+        self.migration = Some(Box::new(migration));
+    }
+
+    pub fn slot_path(&self, slot: u32) -> PathBuf {
+        self.directory.join(format!("slot{slot}.json"))
+    }
+
+    /// Existing save slots in ascending order.
+    pub fn slots(&self) -> Vec<SlotInfo> {
+        let Ok(entries) = fs::read_dir(&self.directory) else {
+            return Vec::new();
+        };
+        let mut slots: Vec<SlotInfo> = entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let name = entry.file_name();
+                let slot = name
+                    .to_str()?
+                    .strip_prefix("slot")?
+                    .strip_suffix(".json")?
+                    .parse()
+                    .ok()?;
+                let modified = entry.metadata().ok()?.modified().ok()?;
+                Some(SlotInfo { slot, modified })
+            })
+            .collect();
+        slots.sort_by_key(|info| info.slot);
+        slots
+    }
+
+    pub fn delete(&self, slot: u32) -> Result<(), Error> {
+        fs::remove_file(self.slot_path(slot)).map_err(|_| Error::FileWriteFailure)
+    }
+
+    /// Capture the state of *scene*. Entities without a save state are skipped.
+    pub fn snapshot(&self, scene: &Scene) -> SaveData {
+        let camera = &scene.scene_info().camera;
+        SaveData {
+            version: self.version,
+            camera: CameraState {
+                center: camera.position(),
+                zoom: camera.zoom(),
+            },
+            time: scene.scheduler.time(),
+            timers: scene.scheduler.keyed_timers(),
+            entities: save_entities(scene),
+            random: scene.random.clone(),
+            emitter_streams: scene.emitter_streams(),
+            custom: Value::Null,
+        }
+    }
+
+    /// Write *data* to *slot*.
+    /// The file is written next to the slot and renamed, so the old save survives a crash.
+    pub fn write(&self, slot: u32, data: &SaveData) -> Result<(), Error> {
+        let json = serde_json::to_vec_pretty(data).map_err(|_| Error::JSONParseFailure)?;
+        fs::create_dir_all(&self.directory).map_err(|_| Error::FileWriteFailure)?;
+        write_atomic(&self.slot_path(slot), &json)
+    }
+
+    /// Read *slot*, migrating it to the current version.
+    pub fn read(&self, slot: u32) -> Result<SaveData, Error> {
+        let json = fs::read(self.slot_path(slot)).map_err(|_| Error::FileReadFailure)?;
+        let mut value: Value =
+            serde_json::from_slice(&json).map_err(|_| Error::JSONParseFailure)?;
+
+        let version = value
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or(Error::JSONParseFailure)? as u32;
+        if version > self.version {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        if version < self.version {
+            let migration = self
+                .migration
+                .as_ref()
+                .ok_or(Error::UnsupportedVersion(version))?;
+            migration(version, &mut value)?;
+            value["version"] = Value::from(self.version);
+        }
+
+        serde_json::from_value(value).map_err(|_| Error::JSONParseFailure)
+    }
+
+    /// Replace the entities and keyed timers of *scene*, and restore the camera and the scheduler time.
+    /// Entities of unknown kinds are skipped.
+    pub fn apply(&self, data: &SaveData, scene: &mut Scene) {
+        scene.clear_entities();
+        // loaded entities by save index, `None` for skipped ones
+        let mut loaded: Vec<Option<Entity>> = Vec::with_capacity(data.entities.len());
+        for saved in data.entities.iter() {
+            let state = &saved.state;
+            let entity = if state.kind == SCRIPT_ENTITY_KIND {
+                scene
+                    .script_host()
+                    .and_then(|host| host.restore_entity(&state.data))
+                    .map(|entity| entity as Entity)
+            } else {
+                self.loaders
+                    .get(&state.kind)
+                    .and_then(|loader| loader(&state.data))
+            };
+            let Some(entity) = entity else {
+                println!("failed to load entity of kind {}", state.kind);
+                loaded.push(None);
+                continue;
+            };
+            let id = scene.add_entity(entity.clone());
+            if let Some(name) = saved.name.as_deref() {
+                scene.set_name(id, name);
+            }
+            for tag in saved.tags.iter() {
+                scene.add_tag(id, tag);
+            }
+            loaded.push(Some(entity));
+        }
+
+        // every entity is in place before the links, so parents may come after children
+        for (saved, child) in data.entities.iter().zip(loaded.iter()) {
+            let parent = saved.parent.and_then(|idx| loaded.get(idx)?.as_ref());
+            if let (Some(child), Some(parent)) = (child, parent) {
+                if let Err(err) = scene.set_parent(child, Some(parent)) {
+                    println!("failed to restore the parent of an entity : {err:?}");
+                }
+            }
+        }
+
+        // the attached entity no longer exists in the scene
+        let camera = &mut scene.scene_info_mut().camera;
+        camera.detach();
+        camera.set_center(data.camera.center);
+        camera.set_zoom(data.camera.zoom);
+        scene.scheduler.set_time(data.time);
+        scene.scheduler.clear_keyed_timers();
+        for timer in data.timers.iter() {
+            scene.scheduler.add_keyed_timer(timer.clone());
+        }
+        scene.random = data.random.clone();
+        // emitters are not saved, the ones in the scene continue the saved streams
+        scene.restore_emitter_streams(&data.emitter_streams);
+    }
+
+    /// Save *scene* to *slot*.
+    pub fn save(&self, slot: u32, scene: &Scene) -> Result<(), Error> {
+        self.write(slot, &self.snapshot(scene))
+    }
+
+    /// Load *slot* into *scene*. Returns the save for its custom data.
+    pub fn load(&self, slot: u32, scene: &mut Scene) -> Result<SaveData, Error> {
+        let data = self.read(slot)?;
+        self.apply(&data, scene);
+        Ok(data)
+    }
+}
+
+/// Entities of *scene* with a save state, with their parents as indices into the result.
+fn save_entities(scene: &Scene) -> Vec<SavedEntity> {
+    let saved: Vec<(&Entity, SavedEntity)> = scene
+        .entities()
+        .iter()
+        .filter_map(|entity| {
+            let state = entity.borrow().save_state()?;
+            let id = scene.id_of(entity)?;
+            let saved = SavedEntity {
+                state,
+                name: scene.name(id),
+                tags: scene.tags(id),
+                parent: None,
+            };
+            Some((entity, saved))
+        })
+        .collect();
+    saved
+        .iter()
+        .map(|(entity, entry)| {
+            let parent = scene.parent(entity).and_then(|parent| {
+                saved
+                    .iter()
+                    .position(|(candidate, _)| Rc::ptr_eq(candidate, &parent))
+            });
+            SavedEntity {
+                parent,
+                ..entry.clone()
+            }
+        })
+        .collect()
+}
+
+/// Write *contents* to a temporary file, flush it to disk and rename it to *path*.
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    let temp_path = path.with_extension("tmp");
+    let mut file = fs::File::create(&temp_path).map_err(|_| Error::FileWriteFailure)?;
+    file.write_all(contents)
+        .and_then(|_| file.sync_all())
+        .map_err(|_| Error::FileWriteFailure)?;
+    fs::rename(&temp_path, path).map_err(|_| Error::FileWriteFailure)
+}
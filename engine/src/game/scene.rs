@@ -1,9 +1,19 @@
+use std::time::Duration;
+
 use crate::game::entity::Entity;
+use crate::game::hierarchy::Hierarchy;
+use crate::game::lifecycle::{Commands, Registry};
+use crate::game::particle::ParticleEmitterHandle;
+use crate::game::scheduler::{self, Scheduler};
+use crate::game::script::ScriptHost;
+use crate::game::spatial::SpatialGrid;
+use crate::game::tween::Animation;
 use crate::game::ui::UIElement;
+use crate::random::{streams, RandomService, Rng};
 use crate::render::Renderer;
 use crate::types::*;
 
-use crate::game::entity::EntityDrawInfo;
+use crate::game::entity::{EntityDrawInfo, RenderLayer};
 
 /// Scene information used to determine what to draw
 pub struct SceneInfo {
@@ -37,16 +47,41 @@ impl Default for Camera {
 impl Camera {
     /// transforms *rect* to the view coordinate
     pub fn transform(&self, rect: Rect) -> Rect {
-        let camera_pos: Vec2 = if let Some(entity) = &self.attachment {
+        let transformed_center = self.transform_point(rect.point_center());
+        let transformed_size = rect.size() * self.zoom;
+        Rect::from_center_size(transformed_center, transformed_size)
+    }
+
+    /// transforms *point* to the view coordinate
+    pub fn transform_point(&self, point: Vec2) -> Vec2 {
+        (point - self.position()) * self.zoom
+    }
+
+    /// world space coordinate of the camera, following the attachment.
+    pub fn position(&self) -> Vec2 {
+        if let Some(entity) = &self.attachment {
             entity.borrow().pos()
         } else {
             self.center
-        };
+        }
+    }
 
-        let object_center = rect.point_center();
-        let transformed_center = (object_center - camera_pos) * self.zoom;
-        let transformed_size = rect.size() * self.zoom;
-        Rect::from_center_size(transformed_center, transformed_size)
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom;
+    }
+
+    /// Move the camera to *center*. It has no effect while attached to an entity.
+    pub fn set_center(&mut self, center: Vec2) {
+        self.center = center;
+    }
+
+    /// world space region visible on a screen of *screen_size*.
+    pub fn visible_rect(&self, screen_size: Vec2) -> Rect {
+        Rect::from_center_size(self.position(), screen_size * (1.0 / self.zoom))
     }
 
     pub fn attach(&mut self, target: Entity) {
@@ -57,12 +92,29 @@ impl Camera {
     }
 }
 
+/// Identifies an animation added by `Scene::add_tween`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TweenId(pub(crate) usize);
+
 /// Scene contains entity + background + UI.
 /// There can be only one active scene at a time.
 pub struct Scene {
     scene_info: SceneInfo,
     ui: Vec<Box<dyn UIElement>>,
-    pub entity_list: Vec<Entity>,
+    pub(crate) entity_list: Vec<Entity>,
+    pub(crate) hierarchy: Hierarchy,
+    pub(crate) registry: Registry,
+    pub(crate) commands: Commands,
+    pub(crate) spatial: SpatialGrid,
+    emitters: Vec<ParticleEmitterHandle>,
+    tweens: Vec<(TweenId, Box<dyn Animation>)>,
+    pub scheduler: Scheduler,
+    /// random streams of the scene. use them instead of other generators to keep replays deterministic.
+    pub random: RandomService,
+    scripts: Option<ScriptHost>,
+    paused: bool,
+    /// whether each layer is sorted by y coordinate. indexed by `RenderLayer`.
+    y_sort: [bool; RenderLayer::COUNT],
     // TODO: background tile
 }
 
@@ -74,79 +126,329 @@ impl Scene {
             },
             ui: Vec::new(),
             entity_list: Vec::new(),
+            hierarchy: Hierarchy::default(),
+            registry: Registry::default(),
+            commands: Commands::default(),
+            spatial: SpatialGrid::default(),
+            emitters: Vec::new(),
+            tweens: Vec::new(),
+            scheduler: Scheduler::default(),
+            random: RandomService::default(),
+            scripts: None,
+            paused: false,
+            y_sort: [false; RenderLayer::COUNT],
         }
     }
 
-    pub fn add_entity(&mut self, entity: Entity) {
-        self.entity_list.push(entity);
+    /// Entities in the order they were added.
+    /// Add and remove them with `add_entity`, `remove_entity` or `Commands`.
+    pub fn entities(&self) -> &[Entity] {
+        &self.entity_list
+    }
+
+    pub fn add_emitter(&mut self, emitter: ParticleEmitterHandle) {
+        emitter.borrow_mut().rng = self.random.stream(streams::PARTICLES).fork();
+        self.emitters.push(emitter);
+    }
+
+    /// Restart the random streams from *seed*.
+    /// `Engine::add_scene` calls it with the engine seed.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.random.reseed(seed);
+        for emitter in self.emitters.iter() {
+            emitter.borrow_mut().rng = self.random.stream(streams::PARTICLES).fork();
+        }
+    }
+
+    /// Random streams of the emitters, in the order they were added.
+    pub(crate) fn emitter_streams(&self) -> Vec<Rng> {
+        self.emitters
+            .iter()
+            .map(|emitter| emitter.borrow().rng.clone())
+            .collect()
+    }
+
+    /// Give the emitters the saved *streams* in order.
+    /// Emitters without a saved stream fork one from the `particles` stream.
+    pub(crate) fn restore_emitter_streams(&mut self, saved: &[Rng]) {
+        for (idx, emitter) in self.emitters.iter().enumerate() {
+            emitter.borrow_mut().rng = match saved.get(idx) {
+                Some(rng) => rng.clone(),
+                None => self.random.stream(streams::PARTICLES).fork(),
+            };
+        }
+    }
+
+    pub fn remove_emitter(&mut self, emitter: &ParticleEmitterHandle) {
+        self.emitters
+            .retain(|candidate| !std::rc::Rc::ptr_eq(candidate, emitter));
+    }
+
+    /// Play *animation* from the next update. It is removed when finished.
+    pub fn add_tween(&mut self, animation: impl Animation + 'static) -> TweenId {
+        let id = self.scheduler.start_tween();
+        self.insert_tween(id, Box::new(animation));
+        id
+    }
+
+    pub(crate) fn insert_tween(&mut self, id: TweenId, animation: Box<dyn Animation>) {
+        self.tweens.push((id, animation));
+    }
+
+    /// Stop the animation without calling its completion callback.
+    pub fn stop_tween(&mut self, id: TweenId) {
+        self.tweens.retain(|(tween_id, _)| *tween_id != id);
+        self.scheduler.finish_tween(id);
+    }
+
+    /// Stop updating entities, particles, tweens and the scheduler.
+    /// The scene is still rendered.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
     }
 
     pub fn add_ui(&mut self, ui: Box<dyn UIElement>) {
         self.ui.push(ui);
     }
 
-    pub fn update(&mut self) {
-        for entity in self.entity_list.iter_mut() {
+    /// Sort entities in *layer* by y coordinate, for top-down games.
+    /// Entities lower on the screen are drawn on top. z-index still takes precedence.
+    pub fn set_y_sort(&mut self, layer: RenderLayer, enabled: bool) {
+        self.y_sort[layer as usize] = enabled;
+    }
+
+    pub fn scene_info(&self) -> &SceneInfo {
+        &self.scene_info
+    }
+
+    pub fn scene_info_mut(&mut self) -> &mut SceneInfo {
+        &mut self.scene_info
+    }
+
+    /// Run scripted entities of *host* in this scene.
+    pub fn set_script_host(&mut self, host: ScriptHost) {
+        self.scripts = Some(host);
+    }
+
+    pub fn script_host(&mut self) -> Option<&mut ScriptHost> {
+        self.scripts.as_mut()
+    }
+
+    /// Forward a key press or release to the scripts. It is ignored while paused.
+    pub fn key_event(&mut self, key: &str, pressed: bool) {
+        if self.paused {
+            return;
+        }
+        if let Some(host) = self.scripts.as_mut() {
+            host.key_event(key, pressed);
+        }
+    }
+
+    /// Advance the scene by one tick. *delta* is the frame time, used by the scheduler.
+    pub fn update(&mut self, delta: Duration) {
+        if self.paused {
+            // entities can still be moved from outside
+            self.refresh_bounds();
+            return;
+        }
+        // commands queued outside of the update, such as by input
+        self.apply_commands();
+
+        for entity in self.entity_list.iter() {
             entity.borrow_mut().update();
         }
+        // the host spawns and removes entities, so it is taken out while running
+        if let Some(mut host) = self.scripts.take() {
+            host.update(self);
+            self.scripts = Some(host);
+        }
+        for emitter in self.emitters.iter() {
+            emitter.borrow_mut().update();
+        }
+
+        let scene_info = &mut self.scene_info;
+        let tween_scheduler = &mut self.scheduler;
+        self.tweens.retain_mut(|(id, animation)| {
+            let finished = animation.update(scene_info);
+            if finished {
+                tween_scheduler.finish_tween(*id);
+            }
+            !finished
+        });
+
+        scheduler::update(self, delta);
+
+        self.apply_commands();
+        // children follow the final position of their parents
+        self.update_hierarchy();
+        self.refresh_bounds();
     }
 
     pub fn render(&self, renderer: &mut Renderer) {
-        for entity in self.entity_list.iter().rev() {
-            let EntityDrawInfo {
-                world_rect,
-                texture,
-                texture_idx,
-            } = entity.borrow().get_draw_info();
-
-            let view_rect = self.scene_info.camera.transform(world_rect);
-
-            match renderer.clip(view_rect) {
-                Some(screen_rect) => {
-                    texture.draw_idx(&mut renderer.canvas, screen_rect, texture_idx);
+        let visible_rect = self
+            .scene_info
+            .camera
+            .visible_rect(renderer.render_info.screen_size);
+
+        // cull with the spatial index before building draw info
+        let visible = self.spatial.query_rect_in_order(visible_rect);
+        renderer.render_info.culled += self.entity_list.len().saturating_sub(visible.len());
+
+        let mut draw_list = Vec::new();
+        for entity in visible.iter().rev() {
+            let entity_ref = entity.borrow();
+            let draw_info = entity_ref.get_draw_info();
+            if !draw_info.world_rect.collides(&visible_rect) {
+                renderer.render_info.culled += 1;
+                continue;
+            }
+
+            let layer = entity_ref.layer();
+            let depth = if self.y_sort[layer as usize] {
+                -draw_info.world_rect.y
+            } else {
+                0
+            };
+            draw_list.push(((layer, entity_ref.z_index(), depth), entity, draw_info));
+        }
+
+        // sort by (layer, z-index, y). stable sort keeps reverse insertion order for ties.
+        draw_list.sort_by_key(|(key, ..)| *key);
+
+        let mut layer_start = 0;
+        for layer in RenderLayer::ALL {
+            let layer_end = layer_start
+                + draw_list[layer_start..]
+                    .partition_point(|((entity_layer, ..), ..)| *entity_layer == layer);
+            let layer_list = &draw_list[layer_start..layer_end];
+            layer_start = layer_end;
+
+            if layer == RenderLayer::UI {
+                self.render_ui(renderer);
+            }
+
+            for (_, _, draw_info) in layer_list {
+                self.render_sprite(renderer, draw_info);
+            }
+            for emitter in self.emitters.iter() {
+                let emitter = emitter.borrow();
+                if emitter.layer == layer {
+                    emitter.render(renderer, &self.scene_info.camera);
                 }
-                None => (),
             }
+            renderer.flush();
+
+            for (_, entity, _) in layer_list {
+                entity.borrow().draw(renderer, &self.scene_info);
+            }
+        }
+    }
+
+    /// Render *root* and its children with the scene camera, sorted like `render`.
+    /// UI and particles are left out.
+    pub fn render_subtree(&self, renderer: &mut Renderer, root: &Entity) {
+        let mut entities = vec![root.clone()];
+        let mut next = 0;
+        while next < entities.len() {
+            let children = self.children(&entities[next]);
+            entities.extend(children);
+            next += 1;
+        }
+
+        let mut draw_list: Vec<_> = entities
+            .iter()
+            .map(|entity| {
+                let entity_ref = entity.borrow();
+                let draw_info = entity_ref.get_draw_info();
+                let layer = entity_ref.layer();
+                let depth = if self.y_sort[layer as usize] {
+                    -draw_info.world_rect.y
+                } else {
+                    0
+                };
+                ((layer, entity_ref.z_index(), depth), entity, draw_info)
+            })
+            .collect();
+        draw_list.sort_by_key(|(key, ..)| *key);
+
+        for (_, entity, draw_info) in draw_list {
+            self.render_sprite(renderer, &draw_info);
+            renderer.flush();
+            entity.borrow().draw(renderer, &self.scene_info);
+        }
+    }
+
+    fn render_sprite(&self, renderer: &mut Renderer, draw_info: &EntityDrawInfo) {
+        let EntityDrawInfo {
+            world_rect,
+            texture,
+            texture_idx,
+        } = draw_info;
+
+        let view_rect = self.scene_info.camera.transform(*world_rect);
+
+        if let Some(screen_rect) = renderer.clip(view_rect) {
+            renderer.draw_sprite(texture, screen_rect, *texture_idx);
         }
+    }
 
+    fn render_ui(&self, renderer: &mut Renderer) {
+        // UI elements draw to the canvas directly
+        renderer.flush();
         for ui in self.ui.iter() {
             ui.draw(renderer, &self.scene_info);
         }
+        if let Some(host) = self.scripts.as_ref() {
+            host.draw_errors(renderer);
+        }
+    }
 
-        // Debug: crosshair at the center
-        let center = Vec2 {
-            x: renderer.render_info.screen_size.x / 2,
-            y: renderer.render_info.screen_size.y / 2,
+    /// Hash of the entity state, the camera, the scheduler time and the random streams,
+    /// including those of the particle emitters.
+    /// The same inputs on the same scene give the same hash, on any machine.
+    pub fn state_hash(&self) -> u64 {
+        // FNV-1a, stable across runs unlike `DefaultHasher`
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut write = |bytes: &[u8]| {
+            for byte in bytes {
+                hash ^= *byte as u64;
+                hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+            }
         };
-        renderer
-            .canvas
-            .set_draw_color(sdl2::pixels::Color::RGB(255, 0, 255));
-        renderer
-            .canvas
-            .draw_line(
-                Vec2 {
-                    x: center.x - 10,
-                    y: center.y,
-                },
-                Vec2 {
-                    x: center.x + 10,
-                    y: center.y,
-                },
-            )
-            .unwrap();
-        renderer
-            .canvas
-            .draw_line(
-                Vec2 {
-                    x: center.x,
-                    y: center.y - 10,
-                },
-                Vec2 {
-                    x: center.x,
-                    y: center.y + 10,
-                },
-            )
-            .unwrap();
+
+        for entity in self.entity_list.iter() {
+            let entity = entity.borrow();
+            let pos = entity.pos();
+            write(&pos.x.to_le_bytes());
+            write(&pos.y.to_le_bytes());
+            write(&[entity.layer() as u8]);
+            write(&entity.z_index().to_le_bytes());
+            if let Some(state) = entity.save_state() {
+                write(state.kind.as_bytes());
+                write(state.data.to_string().as_bytes());
+            }
+        }
+        let camera = &self.scene_info.camera;
+        let camera_pos = camera.position();
+        write(&camera_pos.x.to_le_bytes());
+        write(&camera_pos.y.to_le_bytes());
+        write(&camera.zoom().to_bits().to_le_bytes());
+        write(&self.scheduler.time().as_nanos().to_le_bytes());
+        if let Ok(random) = serde_json::to_string(&self.random) {
+            write(random.as_bytes());
+        }
+        if let Ok(emitter_streams) = serde_json::to_string(&self.emitter_streams()) {
+            write(emitter_streams.as_bytes());
+        }
+        hash
     }
 
     // for debug
@@ -154,7 +456,8 @@ impl Scene {
         self.entity_list.get(0).unwrap().borrow().pos()
     }
     pub fn set_position(&mut self, pos: Vec2) {
-        self.entity_list.get(0).unwrap().borrow_mut().set_pos(pos);
+        let entity = self.entity_list.first().unwrap().clone();
+        entity.borrow_mut().set_pos(pos);
     }
 
     pub fn toggle_camera_attachment(&mut self) {
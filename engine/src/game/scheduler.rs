@@ -0,0 +1,550 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::{Rc, Weak};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::game::entity::{Entity, EntityTrait};
+use crate::game::scene::{Scene, TweenId};
+use crate::game::tween::Animation;
+
+type TimerCallback = Box<dyn FnMut(&mut Scene)>;
+type TimerHandler = Rc<dyn Fn(&mut Scene, &Value)>;
+type Command = Box<dyn FnOnce(&mut Scene)>;
+
+/// Identifies a timer or task added to the `Scheduler`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ScheduleId(usize);
+
+struct Timer {
+    id: ScheduleId,
+    /// scheduler time of the next call
+    due: Duration,
+    interval: Duration,
+    /// remaining calls. `None` repeats forever.
+    remaining: Option<u32>,
+    callback: TimerCallback,
+    /// handler name and payload of keyed timers
+    key: Option<(String, Value)>,
+}
+
+/// Keyed timer as written to a save.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TimerState {
+    /// name given to `Scheduler::register_handler`
+    pub handler: String,
+    /// scheduler time of the next call
+    pub due: Duration,
+    pub interval: Duration,
+    /// remaining calls. `None` repeats forever.
+    pub remaining: Option<u32>,
+    #[serde(default)]
+    pub payload: Value,
+}
+
+struct Task {
+    id: ScheduleId,
+    future: Pin<Box<dyn Future<Output = ()>>>,
+}
+
+/// State shared between the scheduler and its tasks.
+#[derive(Default)]
+struct Shared {
+    time: Duration,
+    /// raised signals with their sequence number
+    signals: Vec<(String, u64)>,
+    signal_seq: u64,
+    active_tweens: HashSet<TweenId>,
+    next_tween_id: usize,
+    /// scene changes requested by tasks, applied after the task yields
+    commands: Vec<Command>,
+}
+
+/// Timers and tasks of a scene.
+///
+/// Time advances by the frame time on every `Scene::update`,
+/// and stops while the scene is paused.
+/// Timers run before tasks, and both run after entities and tweens.
+///
+/// Closures can't be saved, so only keyed timers, which call a handler registered by name
+/// with a JSON payload, are saved by `SaveManager`.
+#[derive(Default)]
+pub struct Scheduler {
+    timers: Vec<Timer>,
+    handlers: HashMap<String, TimerHandler>,
+    tasks: Vec<Task>,
+    next_id: usize,
+    shared: Rc<RefCell<Shared>>,
+
+    running: bool,
+    /// timers and tasks cancelled while they are being run
+    cancelled: HashSet<ScheduleId>,
+}
+
+impl Scheduler {
+    /// Time the scene has been running, excluding pauses.
+    pub fn time(&self) -> Duration {
+        self.shared.borrow().time
+    }
+
+    /// Restore the time of a saved scene. Pending timers keep their due time.
+    pub(crate) fn set_time(&mut self, time: Duration) {
+        self.shared.borrow_mut().time = time;
+    }
+
+    /// Call *callback* once after *delay*.
+    pub fn after<F>(&mut self, delay: Duration, callback: F) -> ScheduleId
+    where
+        F: FnMut(&mut Scene) + 'static,
+    {
+        self.add_timer(delay, Some(1), Box::new(callback))
+    }
+
+    /// Call *callback* every *interval* until cancelled.
+    pub fn every<F>(&mut self, interval: Duration, callback: F) -> ScheduleId
+    where
+        F: FnMut(&mut Scene) + 'static,
+    {
+        self.add_timer(interval, None, Box::new(callback))
+    }
+
+    /// Call *callback* *times* times, every *interval*.
+    /// The first call is after *interval*.
+    pub fn repeat<F>(&mut self, interval: Duration, times: u32, callback: F) -> ScheduleId
+    where
+        F: FnMut(&mut Scene) + 'static,
+    {
+        self.add_timer(interval, Some(times), Box::new(callback))
+    }
+
+    /// Call *handler* with the payload of the keyed timers naming *name*.
+    /// Register it before loading a save, or its timers do nothing when due.
+    pub fn register_handler<F>(&mut self, name: &str, handler: F)
+    where
+        F: Fn(&mut Scene, &Value) + 'static,
+    {
+        self.handlers.insert(name.to_string(), Rc::new(handler));
+    }
+
+    /// Call the handler *handler* with *payload* once after *delay*. The timer is saved.
+    pub fn after_keyed(&mut self, handler: &str, delay: Duration, payload: Value) -> ScheduleId {
+        self.add_keyed_timer(TimerState {
+            handler: handler.to_string(),
+            due: self.time() + delay,
+            interval: delay,
+            remaining: Some(1),
+            payload,
+        })
+    }
+
+    /// Call the handler *handler* with *payload* every *interval* until cancelled. The timer is saved.
+    pub fn every_keyed(&mut self, handler: &str, interval: Duration, payload: Value) -> ScheduleId {
+        self.add_keyed_timer(TimerState {
+            handler: handler.to_string(),
+            due: self.time() + interval,
+            interval,
+            remaining: None,
+            payload,
+        })
+    }
+
+    /// Call the handler *handler* with *payload* *times* times, every *interval*. The timer is saved.
+    pub fn repeat_keyed(
+        &mut self,
+        handler: &str,
+        interval: Duration,
+        times: u32,
+        payload: Value,
+    ) -> ScheduleId {
+        self.add_keyed_timer(TimerState {
+            handler: handler.to_string(),
+            due: self.time() + interval,
+            interval,
+            remaining: Some(times),
+            payload,
+        })
+    }
+
+    /// Add a keyed timer, such as one read from a save.
+    pub fn add_keyed_timer(&mut self, state: TimerState) -> ScheduleId {
+        let id = self.next_schedule_id();
+        let (name, payload) = (state.handler.clone(), state.payload.clone());
+        let callback = move |scene: &mut Scene| {
+            // cloned, so the handler can change the scheduler
+            let handler = scene.scheduler.handlers.get(&name).cloned();
+            match handler {
+                Some(handler) => handler(scene, &payload),
+                None => println!("no timer handler named {name}"),
+            }
+        };
+        self.timers.push(Timer {
+            id,
+            due: state.due,
+            interval: state.interval,
+            remaining: state.remaining,
+            callback: Box::new(callback),
+            key: Some((state.handler, state.payload)),
+        });
+        id
+    }
+
+    /// State of the keyed timers, for saving.
+    pub fn keyed_timers(&self) -> Vec<TimerState> {
+        self.timers
+            .iter()
+            .filter_map(|timer| {
+                let (handler, payload) = timer.key.clone()?;
+                Some(TimerState {
+                    handler,
+                    due: timer.due,
+                    interval: timer.interval,
+                    remaining: timer.remaining,
+                    payload,
+                })
+            })
+            .collect()
+    }
+
+    /// Cancel every keyed timer, before restoring the ones of a save.
+    pub(crate) fn clear_keyed_timers(&mut self) {
+        self.timers.retain(|timer| timer.key.is_none());
+    }
+
+    /// Run an async task, polled once per update until it finishes.
+    ///
+    /// ```ignore
+    /// scene.scheduler.spawn(|ctx| async move {
+    ///     for _ in 0..3 {
+    ///         ctx.wait_seconds(2.0).await;
+    ///         ctx.run(|scene| {
+    ///             scene.add_entity(spawn_enemy());
+    ///         });
+    ///     }
+    ///     ctx.wait_signal("boss_defeated").await;
+    /// });
+    /// ```
+    pub fn spawn<F, Fut>(&mut self, task: F) -> ScheduleId
+    where
+        F: FnOnce(TaskContext) -> Fut,
+        Fut: Future<Output = ()> + 'static,
+    {
+        let id = self.next_schedule_id();
+        let context = TaskContext {
+            shared: self.shared.clone(),
+        };
+        self.tasks.push(Task {
+            id,
+            future: Box::pin(task(context)),
+        });
+        id
+    }
+
+    /// Stop a timer or task. Cancelled tasks are dropped at the await point.
+    pub fn cancel(&mut self, id: ScheduleId) {
+        self.timers.retain(|timer| timer.id != id);
+        self.tasks.retain(|task| task.id != id);
+        if self.running {
+            self.cancelled.insert(id);
+        }
+    }
+
+    /// Raise signal *name*. Tasks waiting for it resume on their next poll.
+    pub fn signal(&mut self, name: &str) {
+        raise_signal(&mut self.shared.borrow_mut(), name);
+    }
+
+    fn add_timer(
+        &mut self,
+        interval: Duration,
+        remaining: Option<u32>,
+        callback: TimerCallback,
+    ) -> ScheduleId {
+        let id = self.next_schedule_id();
+        self.timers.push(Timer {
+            id,
+            due: self.time() + interval,
+            interval,
+            remaining,
+            callback,
+            key: None,
+        });
+        id
+    }
+
+    fn next_schedule_id(&mut self) -> ScheduleId {
+        let id = ScheduleId(self.next_id);
+        self.next_id += 1;
+        id
+    }
+
+    /// Allocate an id for a tween and mark it as playing.
+    pub(crate) fn start_tween(&mut self) -> TweenId {
+        start_tween(&mut self.shared.borrow_mut())
+    }
+
+    pub(crate) fn finish_tween(&mut self, id: TweenId) {
+        self.shared.borrow_mut().active_tweens.remove(&id);
+    }
+}
+
+fn raise_signal(shared: &mut Shared, name: &str) {
+    shared.signal_seq += 1;
+    let seq = shared.signal_seq;
+    shared.signals.push((name.to_string(), seq));
+}
+
+fn start_tween(shared: &mut Shared) -> TweenId {
+    let id = TweenId(shared.next_tween_id);
+    shared.next_tween_id += 1;
+    shared.active_tweens.insert(id);
+    id
+}
+
+/// Advance the scheduler of *scene* by *delta*, running due timers and polling tasks.
+pub(crate) fn update(scene: &mut Scene, delta: Duration) {
+    // callbacks get the whole scene, so run them outside of the scheduler
+    let scheduler = &mut scene.scheduler;
+    let mut timers = std::mem::take(&mut scheduler.timers);
+    let mut tasks = std::mem::take(&mut scheduler.tasks);
+    scheduler.running = true;
+    let (time, signal_mark) = {
+        let mut shared = scheduler.shared.borrow_mut();
+        shared.time += delta;
+        (shared.time, shared.signal_seq)
+    };
+
+    for timer in timers.iter_mut() {
+        while timer.due <= time
+            && timer.remaining != Some(0)
+            && !scene.scheduler.cancelled.contains(&timer.id)
+        {
+            (timer.callback)(scene);
+            timer.remaining = timer.remaining.map(|remaining| remaining - 1);
+            timer.due += timer.interval;
+            // zero interval timers run once per update
+            if timer.interval.is_zero() {
+                break;
+            }
+        }
+    }
+
+    let mut context = Context::from_waker(Waker::noop());
+    tasks.retain_mut(|task| {
+        if scene.scheduler.cancelled.contains(&task.id) {
+            return false;
+        }
+        let finished = task.future.as_mut().poll(&mut context).is_ready();
+        let commands = std::mem::take(&mut scene.scheduler.shared.borrow_mut().commands);
+        for command in commands {
+            command(scene);
+        }
+        !finished
+    });
+
+    // keep timers and tasks added by the callbacks
+    let scheduler = &mut scene.scheduler;
+    timers.append(&mut scheduler.timers);
+    tasks.append(&mut scheduler.tasks);
+    timers.retain(|timer| timer.remaining != Some(0) && !scheduler.cancelled.contains(&timer.id));
+    tasks.retain(|task| !scheduler.cancelled.contains(&task.id));
+    scheduler.timers = timers;
+    scheduler.tasks = tasks;
+    scheduler.cancelled.clear();
+    scheduler.running = false;
+
+    // every task has seen the signals raised before this update
+    scheduler
+        .shared
+        .borrow_mut()
+        .signals
+        .retain(|(_, seq)| *seq > signal_mark);
+}
+
+/// Handle given to async tasks to wait and to change the scene.
+#[derive(Clone)]
+pub struct TaskContext {
+    shared: Rc<RefCell<Shared>>,
+}
+
+impl TaskContext {
+    /// Time the scene has been running, excluding pauses.
+    pub fn time(&self) -> Duration {
+        self.shared.borrow().time
+    }
+
+    /// Run *command* with the scene after the task yields.
+    pub fn run<F>(&self, command: F)
+    where
+        F: FnOnce(&mut Scene) + 'static,
+    {
+        self.shared.borrow_mut().commands.push(Box::new(command));
+    }
+
+    /// Play *animation* in the scene. Await it with `wait_tween`.
+    pub fn add_tween(&self, animation: impl Animation + 'static) -> TweenId {
+        let id = start_tween(&mut self.shared.borrow_mut());
+        self.run(move |scene| scene.insert_tween(id, Box::new(animation)));
+        id
+    }
+
+    /// Raise signal *name*. Tasks waiting for it resume on their next poll.
+    pub fn signal(&self, name: &str) {
+        raise_signal(&mut self.shared.borrow_mut(), name);
+    }
+
+    /// Wait for *duration*.
+    pub fn wait(&self, duration: Duration) -> Wait {
+        Wait {
+            shared: self.shared.clone(),
+            until: self.time() + duration,
+        }
+    }
+
+    /// Wait for *seconds*.
+    pub fn wait_seconds(&self, seconds: f32) -> Wait {
+        self.wait(Duration::from_secs_f32(seconds.max(0.0)))
+    }
+
+    /// Wait until the next update.
+    pub fn next_update(&self) -> NextUpdate {
+        NextUpdate { polled: false }
+    }
+
+    /// Wait until signal *name* is raised.
+    /// Signals raised before the call are not seen.
+    pub fn wait_signal(&self, name: &str) -> WaitSignal {
+        let since = self.shared.borrow().signal_seq;
+        WaitSignal {
+            shared: self.shared.clone(),
+            name: name.to_string(),
+            since,
+        }
+    }
+
+    /// Wait until the animation finishes or is stopped.
+    pub fn wait_tween(&self, id: TweenId) -> WaitTween {
+        WaitTween {
+            shared: self.shared.clone(),
+            id,
+        }
+    }
+
+    /// Wait until the sprite animation of *entity* reaches its last frame,
+    /// counted by `EntityTrait::animation_loops`. It also resumes if the entity is dropped.
+    pub fn wait_animation(&self, entity: &Entity) -> WaitAnimation {
+        WaitAnimation {
+            entity: Rc::downgrade(entity),
+            loops: entity.borrow().animation_loops(),
+        }
+    }
+}
+
+/// Future returned by `TaskContext::wait`.
+pub struct Wait {
+    shared: Rc<RefCell<Shared>>,
+    until: Duration,
+}
+
+impl Future for Wait {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _context: &mut Context<'_>) -> Poll<()> {
+        if self.shared.borrow().time >= self.until {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Future returned by `TaskContext::next_update`.
+pub struct NextUpdate {
+    polled: bool,
+}
+
+impl Future for NextUpdate {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _context: &mut Context<'_>) -> Poll<()> {
+        if self.polled {
+            Poll::Ready(())
+        } else {
+            self.polled = true;
+            Poll::Pending
+        }
+    }
+}
+
+/// Future returned by `TaskContext::wait_signal`.
+pub struct WaitSignal {
+    shared: Rc<RefCell<Shared>>,
+    name: String,
+    since: u64,
+}
+
+impl Future for WaitSignal {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _context: &mut Context<'_>) -> Poll<()> {
+        let raised = self
+            .shared
+            .borrow()
+            .signals
+            .iter()
+            .any(|(name, seq)| *seq > self.since && *name == self.name);
+        if raised {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Future returned by `TaskContext::wait_tween`.
+pub struct WaitTween {
+    shared: Rc<RefCell<Shared>>,
+    id: TweenId,
+}
+
+impl Future for WaitTween {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _context: &mut Context<'_>) -> Poll<()> {
+        if self.shared.borrow().active_tweens.contains(&self.id) {
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    }
+}
+
+/// Future returned by `TaskContext::wait_animation`.
+pub struct WaitAnimation {
+    entity: Weak<RefCell<dyn EntityTrait>>,
+    /// loops when the wait started
+    loops: u64,
+}
+
+impl Future for WaitAnimation {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _context: &mut Context<'_>) -> Poll<()> {
+        let Some(entity) = self.entity.upgrade() else {
+            return Poll::Ready(());
+        };
+        // an entity borrowed elsewhere is checked on the next poll
+        let looped = entity
+            .try_borrow()
+            .is_ok_and(|entity| entity.animation_loops() > self.loops);
+        if looped {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
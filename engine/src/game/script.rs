@@ -0,0 +1,613 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::{Rc, Weak};
+
+use rhai::{CallFnOptions, Dynamic, FuncArgs, ImmutableString, Map, Scope, AST, FLOAT, INT};
+use serde_json::{json, Value};
+
+use crate::error::Error;
+use crate::game::entity::{
+    entity_key, Entity, EntityDrawInfo, EntityState, EntityTrait, RenderLayer,
+};
+use crate::game::save::SCRIPT_ENTITY_KIND;
+use crate::game::scene::Scene;
+use crate::render::primitive::Space;
+use crate::render::texture::Texture;
+use crate::render::Renderer;
+use crate::types::*;
+use crate::vfs::Vfs;
+
+/// number of script errors kept for display
+const MAX_ERRORS: usize = 8;
+/// operations a single callback may run before it is aborted
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// Compiled script shared by every entity using it.
+pub type Script = Rc<ScriptSource>;
+
+pub struct ScriptSource {
+    name: String,
+    ast: AST,
+}
+
+impl ScriptSource {
+    fn has_fn(&self, name: &str) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|function| function.name == name)
+    }
+}
+
+/// Requests from scripts, applied to the scene after entities are updated.
+enum ScriptCommand {
+    Spawn { prefab: String, position: Vec2 },
+    PlaySound(String),
+    Signal(String),
+    CameraCenter(Vec2),
+    CameraZoom(f32),
+}
+
+/// State shared between the host and the functions registered to the script engine.
+#[derive(Default)]
+struct HostState {
+    commands: Vec<ScriptCommand>,
+    pressed_keys: HashSet<String>,
+    errors: Vec<String>,
+}
+
+impl HostState {
+    fn report(&mut self, message: String) {
+        println!("script error : {message}");
+        if self.errors.len() == MAX_ERRORS {
+            self.errors.remove(0);
+        }
+        self.errors.push(message);
+    }
+}
+
+/// Script and texture spawned by name from scripts.
+struct Prefab {
+    script: Script,
+    texture: Texture,
+    size: Vec2,
+}
+
+type SoundHandler = Box<dyn FnMut(&str)>;
+
+/// Rhai scripting for entities. Add it to a scene with `Scene::set_script_host`.
+///
+/// Scripts define any of these callbacks. `this` is an object map of the entity.
+///
+/// ```text
+/// fn init() { this.speed = 4; }
+/// fn update() { if is_key_down("Right") { this.x += this.speed; } }
+/// fn on_collision(other) { if other.kind == "coin" { play_sound("coin"); } }
+/// fn on_key(key, pressed) { if key == "Space" && pressed { spawn("bullet", this.x, this.y); } }
+/// ```
+///
+/// `this` has `x`, `y`, `width`, `height`, `frame`, `z_index`, `solid` and `alive`,
+/// which are read back after every callback, and any field the script adds.
+/// Setting `this.animation` to an array of frames plays it every `this.frame_delay` ticks.
+/// Setting `alive` to `false` removes the entity from the scene.
+///
+/// A script that fails is disabled, and the error is shown on screen.
+pub struct ScriptHost {
+    engine: Rc<rhai::Engine>,
+    state: Rc<RefCell<HostState>>,
+    prefabs: HashMap<String, Prefab>,
+    entities: Vec<Weak<RefCell<ScriptEntity>>>,
+    sound_handler: Option<SoundHandler>,
+}
+
+impl Default for ScriptHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScriptHost {
+    pub fn new() -> Self {
+        let state = Rc::new(RefCell::new(HostState::default()));
+        let mut engine = rhai::Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.on_print(|text| println!("script : {text}"));
+
+        let commands = state.clone();
+        engine.register_fn("spawn", move |prefab: &str, x: INT, y: INT| {
+            commands.borrow_mut().commands.push(ScriptCommand::Spawn {
+                prefab: prefab.to_string(),
+                position: Vec2 {
+                    x: x as i32,
+                    y: y as i32,
+                },
+            });
+        });
+        let commands = state.clone();
+        engine.register_fn("play_sound", move |name: &str| {
+            commands
+                .borrow_mut()
+                .commands
+                .push(ScriptCommand::PlaySound(name.to_string()));
+        });
+        let commands = state.clone();
+        engine.register_fn("signal", move |name: &str| {
+            commands
+                .borrow_mut()
+                .commands
+                .push(ScriptCommand::Signal(name.to_string()));
+        });
+        let commands = state.clone();
+        engine.register_fn("set_camera_center", move |x: INT, y: INT| {
+            commands
+                .borrow_mut()
+                .commands
+                .push(ScriptCommand::CameraCenter(Vec2 {
+                    x: x as i32,
+                    y: y as i32,
+                }));
+        });
+        let commands = state.clone();
+        engine.register_fn("set_camera_zoom", move |zoom: FLOAT| {
+            commands
+                .borrow_mut()
+                .commands
+                .push(ScriptCommand::CameraZoom(zoom as f32));
+        });
+        let keys = state.clone();
+        engine.register_fn("is_key_down", move |key: &str| {
+            keys.borrow().pressed_keys.contains(key)
+        });
+
+        Self {
+            engine: Rc::new(engine),
+            state,
+            prefabs: HashMap::new(),
+            entities: Vec::new(),
+            sound_handler: None,
+        }
+    }
+
+    /// Compile the script file at the virtual *path*.
+    pub fn load_script(&self, vfs: &Vfs, path: &str) -> Result<Script, Error> {
+        let source = vfs.read_to_string(path)?;
+        let name = path.rsplit('/').next().unwrap_or(path);
+        self.compile(name.trim_end_matches(".rhai"), &source)
+    }
+
+    /// Compile *source*. *name* is shown in error messages.
+    pub fn compile(&self, name: &str, source: &str) -> Result<Script, Error> {
+        let ast = self
+            .engine
+            .compile(source)
+            .map_err(|err| Error::ScriptCompileFailure(format!("{name} : {err}")))?;
+        Ok(Rc::new(ScriptSource {
+            name: name.to_string(),
+            ast,
+        }))
+    }
+
+    /// Register a prefab scripts can create with `spawn(name, x, y)`.
+    pub fn add_prefab(&mut self, name: &str, script: Script, texture: Texture, size: Vec2) {
+        self.prefabs.insert(
+            name.to_string(),
+            Prefab {
+                script,
+                texture,
+                size,
+            },
+        );
+    }
+
+    /// Called with the sound name when a script calls `play_sound(name)`.
+    pub fn set_sound_handler<F>(&mut self, handler: F)
+    where
+        F: FnMut(&str) + 'static,
+    {
+        self.sound_handler = Some(Box::new(handler));
+    }
+
+    /// Create an entity running *script*. `init` is called before returning.
+    /// Add it to the scene with `Scene::add_entity`.
+    pub fn create_entity(
+        &mut self,
+        script: Script,
+        texture: Texture,
+        position: Vec2,
+        size: Vec2,
+    ) -> Rc<RefCell<ScriptEntity>> {
+        let mut this = Map::new();
+        this.insert("kind".into(), script.name.clone().into());
+        this.insert("frame_delay".into(), Dynamic::from_int(10));
+        let entity = Rc::new(RefCell::new(ScriptEntity {
+            script,
+            texture,
+            engine: self.engine.clone(),
+            state: self.state.clone(),
+            this: Dynamic::from_map(this),
+            scope: Scope::new(),
+            position,
+            size,
+            frame: 0,
+            animation: Vec::new(),
+            frame_delay: 10,
+            frame_timer: 0,
+            z_index: 0,
+            layer: RenderLayer::World,
+            solid: true,
+            alive: true,
+            failed: false,
+            prefab: None,
+        }));
+        entity.borrow_mut().call("init", ());
+        self.entities.push(Rc::downgrade(&entity));
+        entity
+    }
+
+    /// Create the prefab *name* at *position*. `None` if it is not registered.
+    pub fn spawn(&mut self, name: &str, position: Vec2) -> Option<Rc<RefCell<ScriptEntity>>> {
+        let prefab = self.prefabs.get(name)?;
+        let (script, texture, size) = (prefab.script.clone(), prefab.texture.clone(), prefab.size);
+        let entity = self.create_entity(script, texture, position, size);
+        entity.borrow_mut().prefab = Some(name.to_string());
+        Some(entity)
+    }
+
+    /// Spawn the prefab of a saved entity and restore its `this`.
+    pub(crate) fn restore_entity(&mut self, data: &Value) -> Option<Rc<RefCell<ScriptEntity>>> {
+        let prefab = data.get("prefab")?.as_str()?;
+        let this: Dynamic = serde_json::from_value(data.get("this")?.clone()).ok()?;
+        let entity = self.spawn(prefab, Vec2::default())?;
+        {
+            let mut entity_mut = entity.borrow_mut();
+            entity_mut.this = this;
+            entity_mut.read_this();
+        }
+        Some(entity)
+    }
+
+    /// Errors of the failed scripts, oldest first.
+    pub fn errors(&self) -> Vec<String> {
+        self.state.borrow().errors.clone()
+    }
+
+    pub fn clear_errors(&mut self) {
+        self.state.borrow_mut().errors.clear();
+    }
+
+    /// Track the key state and call `on_key` of every script entity.
+    /// *key* is the SDL key name such as `"W"`, `"Space"` or `"Left"`.
+    pub fn key_event(&mut self, key: &str, pressed: bool) {
+        {
+            let mut state = self.state.borrow_mut();
+            if pressed {
+                state.pressed_keys.insert(key.to_string());
+            } else {
+                state.pressed_keys.remove(key);
+            }
+        }
+        for entity in self.entities.iter().filter_map(Weak::upgrade) {
+            let key = ImmutableString::from(key);
+            entity.borrow_mut().call("on_key", (key, pressed));
+        }
+    }
+
+    /// Remove dead entities, report collisions and apply requests from scripts.
+    pub(crate) fn update(&mut self, scene: &mut Scene) {
+        self.entities.retain(|entity| entity.strong_count() > 0);
+
+        // despawn, together with the children
+        let dead: Vec<Rc<RefCell<ScriptEntity>>> = self
+            .entities
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|entity| !entity.borrow().alive)
+            .collect();
+        for entity in dead {
+            scene.remove_entity(&(entity as Entity));
+        }
+        self.entities.retain(|entity| entity.strong_count() > 0);
+
+        // collisions are looked up in the spatial index, so bring it up to date first
+        scene.refresh_bounds();
+        self.report_collisions(scene);
+
+        let commands = std::mem::take(&mut self.state.borrow_mut().commands);
+        for command in commands {
+            match command {
+                ScriptCommand::Spawn { prefab, position } => match self.spawn(&prefab, position) {
+                    Some(entity) => {
+                        scene.add_entity(entity);
+                    }
+                    None => self
+                        .state
+                        .borrow_mut()
+                        .report(format!("spawn : no prefab named {prefab}")),
+                },
+                ScriptCommand::PlaySound(name) => {
+                    if let Some(handler) = self.sound_handler.as_mut() {
+                        handler(&name);
+                    }
+                }
+                ScriptCommand::Signal(name) => scene.scheduler.signal(&name),
+                ScriptCommand::CameraCenter(center) => {
+                    scene.scene_info_mut().camera.set_center(center)
+                }
+                ScriptCommand::CameraZoom(zoom) => scene.scene_info_mut().camera.set_zoom(zoom),
+            }
+        }
+    }
+
+    /// Call `on_collision` of script entities overlapping another entity, every tick.
+    /// The argument is the `this` of the other script entity,
+    /// or a map with the position and size of a native entity.
+    /// Candidates come from the spatial index, so colliders must lie inside the bounds.
+    fn report_collisions(&mut self, scene: &Scene) {
+        let script_entities: HashMap<usize, Rc<RefCell<ScriptEntity>>> = self
+            .entities
+            .iter()
+            .filter_map(Weak::upgrade)
+            .map(|entity| (entity_key(&(entity.clone() as Entity)), entity))
+            .collect();
+
+        // in spawn and insertion order, so replays report the same collisions
+        for entity in self.entities.iter().filter_map(Weak::upgrade) {
+            let key = entity_key(&(entity.clone() as Entity));
+            let Some(collider) = entity.borrow().collider() else {
+                continue;
+            };
+            for other in scene.spatial.query_rect_in_order(collider) {
+                let other_key = entity_key(&other);
+                if other_key == key {
+                    continue;
+                }
+                let Some(other_collider) = other.borrow().collider() else {
+                    continue;
+                };
+                if !collider.collides(&other_collider) {
+                    continue;
+                }
+                let info = match script_entities.get(&other_key) {
+                    Some(script_entity) => script_entity.borrow().this.clone(),
+                    None => {
+                        let center = other_collider.point_center();
+                        let mut info = Map::new();
+                        info.insert("x".into(), Dynamic::from_int(center.x as INT));
+                        info.insert("y".into(), Dynamic::from_int(center.y as INT));
+                        info.insert("width".into(), Dynamic::from_int(other_collider.w as INT));
+                        info.insert("height".into(), Dynamic::from_int(other_collider.h as INT));
+                        Dynamic::from_map(info)
+                    }
+                };
+                entity.borrow_mut().call("on_collision", (info,));
+            }
+        }
+    }
+
+    /// Show script errors at the bottom of the screen.
+    pub(crate) fn draw_errors(&self, renderer: &mut Renderer) {
+        let state = self.state.borrow();
+        if state.errors.is_empty() {
+            return;
+        }
+
+        let glyph = Vec2 { x: 8, y: 16 };
+        let screen_size = renderer.render_info.screen_size;
+        let height = state.errors.len() as i32 * glyph.y + 8;
+        renderer.fill_rect(
+            Space::Screen,
+            Rect {
+                x: 0,
+                y: screen_size.y - height,
+                w: screen_size.x,
+                h: height,
+            },
+            Color::rgba(128, 0, 0, 200),
+        );
+
+        let Some(font) = renderer.fonts.first().cloned() else {
+            return;
+        };
+        for (idx, error) in state.errors.iter().enumerate() {
+            font.draw(
+                &mut renderer.canvas,
+                error,
+                Vec2 {
+                    x: 4,
+                    y: screen_size.y - height + 4 + idx as i32 * glyph.y,
+                },
+                glyph,
+            );
+        }
+    }
+}
+
+/// Entity whose behavior is defined by a script. Create it with `ScriptHost::create_entity`.
+pub struct ScriptEntity {
+    script: Script,
+    texture: Texture,
+    engine: Rc<rhai::Engine>,
+    state: Rc<RefCell<HostState>>,
+    /// object map bound to `this` in callbacks
+    this: Dynamic,
+    scope: Scope<'static>,
+
+    position: Vec2,
+    size: Vec2,
+    frame: usize,
+    animation: Vec<usize>,
+    frame_delay: usize,
+    frame_timer: usize,
+    z_index: i32,
+    layer: RenderLayer,
+    solid: bool,
+    alive: bool,
+    /// set when a callback failed. no callback is called afterwards.
+    failed: bool,
+    /// prefab the entity was spawned from, used to recreate it from a save
+    prefab: Option<String>,
+}
+
+impl ScriptEntity {
+    pub fn set_layer(&mut self, layer: RenderLayer) {
+        self.layer = layer;
+    }
+
+    /// Value of field *name* of `this`.
+    pub fn get(&self, name: &str) -> Option<Dynamic> {
+        self.this.read_lock::<Map>()?.get(name).cloned()
+    }
+
+    /// Set field *name* of `this`.
+    pub fn set(&mut self, name: &str, value: Dynamic) {
+        if let Some(mut this) = self.this.write_lock::<Map>() {
+            this.insert(name.into(), value);
+        }
+        self.read_this();
+    }
+
+    /// Call *function* of the script if it is defined.
+    fn call(&mut self, function: &str, args: impl FuncArgs) {
+        if self.failed || !self.script.has_fn(function) {
+            return;
+        }
+        self.write_this();
+
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut self.this);
+        let result = self.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut self.scope,
+            &self.script.ast,
+            function,
+            args,
+        );
+        match result {
+            Ok(_) => self.read_this(),
+            Err(err) => {
+                self.failed = true;
+                self.state
+                    .borrow_mut()
+                    .report(format!("{} {function} : {err}", self.script.name));
+            }
+        }
+    }
+
+    /// Copy the entity state into `this`.
+    fn write_this(&mut self) {
+        let Some(mut this) = self.this.write_lock::<Map>() else {
+            return;
+        };
+        let int = |value: i32| Dynamic::from_int(value as INT);
+        this.insert("x".into(), int(self.position.x));
+        this.insert("y".into(), int(self.position.y));
+        this.insert("width".into(), int(self.size.x));
+        this.insert("height".into(), int(self.size.y));
+        this.insert("frame".into(), int(self.frame as i32));
+        this.insert("frame_count".into(), int(self.texture.len() as i32));
+        this.insert("z_index".into(), int(self.z_index));
+        this.insert("solid".into(), Dynamic::from_bool(self.solid));
+        this.insert("alive".into(), Dynamic::from_bool(self.alive));
+    }
+
+    /// Read the entity state back from `this`. Fields of a wrong type are ignored.
+    fn read_this(&mut self) {
+        let Some(this) = self.this.read_lock::<Map>() else {
+            return;
+        };
+        let number = |name: &str| {
+            let value = this.get(name)?;
+            value
+                .as_int()
+                .map(|value| value as i32)
+                .or_else(|_| value.as_float().map(|value| value.round() as i32))
+                .ok()
+        };
+        let boolean = |name: &str| this.get(name).and_then(|value| value.as_bool().ok());
+
+        self.position.x = number("x").unwrap_or(self.position.x);
+        self.position.y = number("y").unwrap_or(self.position.y);
+        self.size.x = number("width").unwrap_or(self.size.x);
+        self.size.y = number("height").unwrap_or(self.size.y);
+        self.frame = number("frame").map_or(self.frame, |frame| frame.max(0) as usize);
+        self.z_index = number("z_index").unwrap_or(self.z_index);
+        self.frame_delay =
+            number("frame_delay").map_or(self.frame_delay, |delay| delay.max(1) as usize);
+        self.solid = boolean("solid").unwrap_or(self.solid);
+        self.alive = boolean("alive").unwrap_or(self.alive);
+        self.animation = this
+            .get("animation")
+            .and_then(|animation| {
+                animation
+                    .read_lock::<rhai::Array>()
+                    .map(|array| array.clone())
+            })
+            .map(|array| {
+                array
+                    .iter()
+                    .filter_map(|frame| frame.as_int().ok())
+                    .map(|frame| frame.max(0) as usize)
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
+}
+
+impl EntityTrait for ScriptEntity {
+    fn pos(&self) -> Vec2 {
+        self.position
+    }
+    fn set_pos(&mut self, new_pos: Vec2) {
+        self.position = new_pos;
+    }
+
+    fn update(&mut self) {
+        if !self.animation.is_empty() {
+            self.frame_timer += 1;
+            if self.frame_timer >= self.frame_delay {
+                self.frame_timer = 0;
+                let idx = self
+                    .animation
+                    .iter()
+                    .position(|frame| *frame == self.frame)
+                    .map_or(0, |idx| (idx + 1) % self.animation.len());
+                self.frame = self.animation[idx];
+            }
+        }
+        self.call("update", ());
+    }
+
+    fn layer(&self) -> RenderLayer {
+        self.layer
+    }
+    fn z_index(&self) -> i32 {
+        self.z_index
+    }
+
+    fn bounds(&self) -> Option<Rect> {
+        Some(Rect::from_center_size(self.position, self.size))
+    }
+
+    fn collider(&self) -> Option<Rect> {
+        self.solid
+            .then(|| Rect::from_center_size(self.position, self.size))
+    }
+
+    fn get_draw_info(&self) -> EntityDrawInfo {
+        EntityDrawInfo {
+            world_rect: Rect::from_center_size(self.position, self.size),
+            texture: self.texture.clone(),
+            texture_idx: self.frame.min(self.texture.len().saturating_sub(1)),
+        }
+    }
+
+    /// Only entities spawned from a prefab are saved.
+    fn save_state(&self) -> Option<EntityState> {
+        let prefab = self.prefab.as_ref()?;
+        let this = serde_json::to_value(&self.this).ok()?;
+        Some(EntityState {
+            kind: String::from(SCRIPT_ENTITY_KIND),
+            data: json!({ "prefab": prefab, "this": this }),
+        })
+    }
+}
@@ -0,0 +1,590 @@
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+
+use crate::game::entity::{entity_key, Entity};
+use crate::game::scene::Scene;
+use crate::types::*;
+
+/// Hasher for entity addresses. They are already unique, so mixing the bits is enough.
+#[derive(Default)]
+struct AddressHasher(u64);
+
+impl Hasher for AddressHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100_0000_01b3);
+        }
+    }
+
+    fn write_usize(&mut self, address: usize) {
+        // Fibonacci hashing spreads the aligned addresses over the table
+        self.0 = (address as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    }
+}
+
+type AddressMap<V> = HashMap<usize, V, BuildHasherDefault<AddressHasher>>;
+
+/// default cell size in world units
+pub const DEFAULT_CELL_SIZE: i32 = 512;
+
+/// cell coordinates covered by a rect, inclusive
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct CellRange {
+    min_x: i32,
+    min_y: i32,
+    max_x: i32,
+    max_y: i32,
+}
+
+struct Entry {
+    entity: Entity,
+    bounds: Rect,
+    cells: CellRange,
+    /// insertion order, used to keep draw order stable
+    order: u64,
+    /// last refresh the entity was seen in
+    generation: u64,
+}
+
+/// Entity found by `SpatialGrid::raycast`.
+pub struct RayHit {
+    pub entity: Entity,
+    /// distance from the ray origin in world units
+    pub distance: f32,
+    pub point: Vec2,
+}
+
+/// Uniform grid of entity bounds.
+///
+/// Entities are stored in every cell their bounds overlap.
+/// The scene refreshes it at the end of every update, moving only entities that moved.
+pub struct SpatialGrid {
+    cell_size: i32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    /// keyed by entity address
+    entries: AddressMap<Entry>,
+    next_order: u64,
+    generation: u64,
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        Self::new(DEFAULT_CELL_SIZE)
+    }
+}
+
+impl SpatialGrid {
+    /// Cells of *cell_size* world units. Use about twice the size of a typical entity.
+    pub fn new(cell_size: i32) -> Self {
+        Self {
+            cell_size: cell_size.max(1),
+            cells: HashMap::new(),
+            entries: AddressMap::default(),
+            next_order: 0,
+            generation: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.entries.clear();
+    }
+
+    fn cell_range(&self, rect: Rect) -> CellRange {
+        let top_right = rect.point_right_top();
+        CellRange {
+            min_x: rect.x.div_euclid(self.cell_size),
+            min_y: rect.y.div_euclid(self.cell_size),
+            max_x: top_right.x.div_euclid(self.cell_size),
+            max_y: top_right.y.div_euclid(self.cell_size),
+        }
+    }
+
+    /// Insert *entity* or move it to *bounds*.
+    pub fn update(&mut self, entity: &Entity, bounds: Rect) {
+        let key = entity_key(entity);
+        let cells = self.cell_range(bounds);
+        let generation = self.generation;
+
+        let old_cells = match self.entries.get_mut(&key) {
+            Some(entry) => {
+                entry.bounds = bounds;
+                entry.generation = generation;
+                if entry.cells == cells {
+                    return;
+                }
+                let old_cells = entry.cells;
+                entry.cells = cells;
+                Some(old_cells)
+            }
+            None => {
+                self.entries.insert(
+                    key,
+                    Entry {
+                        entity: entity.clone(),
+                        bounds,
+                        cells,
+                        order: self.next_order,
+                        generation,
+                    },
+                );
+                self.next_order += 1;
+                None
+            }
+        };
+
+        if let Some(old_cells) = old_cells {
+            self.remove_from_cells(key, old_cells);
+        }
+        for x in cells.min_x..=cells.max_x {
+            for y in cells.min_y..=cells.max_y {
+                self.cells.entry((x, y)).or_default().push(key);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, entity: &Entity) {
+        let key = entity_key(entity);
+        if let Some(entry) = self.entries.remove(&key) {
+            self.remove_from_cells(key, entry.cells);
+        }
+    }
+
+    fn remove_from_cells(&mut self, key: usize, cells: CellRange) {
+        for x in cells.min_x..=cells.max_x {
+            for y in cells.min_y..=cells.max_y {
+                if let Some(cell) = self.cells.get_mut(&(x, y)) {
+                    cell.retain(|candidate| *candidate != key);
+                    if cell.is_empty() {
+                        self.cells.remove(&(x, y));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Update every entity in *entities* and remove the ones that are gone.
+    pub(crate) fn refresh(&mut self, entities: &[Entity]) {
+        self.generation += 1;
+        for entity in entities {
+            self.update(entity, entity_bounds(entity));
+        }
+
+        let generation = self.generation;
+        let stale: Vec<Entity> = self
+            .entries
+            .values()
+            .filter(|entry| entry.generation != generation)
+            .map(|entry| entry.entity.clone())
+            .collect();
+        for entity in stale.iter() {
+            self.remove(entity);
+        }
+    }
+
+    /// Visit each entry overlapping *rect* once.
+    fn for_each_in_rect<F>(&self, rect: Rect, mut visit: F)
+    where
+        F: FnMut(&Entry),
+    {
+        let query = self.cell_range(rect);
+        for x in query.min_x..=query.max_x {
+            for y in query.min_y..=query.max_y {
+                let Some(cell) = self.cells.get(&(x, y)) else {
+                    continue;
+                };
+                for key in cell {
+                    let entry = &self.entries[key];
+                    // an entry spanning several cells is visited only in the first shared cell
+                    let first_x = entry.cells.min_x.max(query.min_x);
+                    let first_y = entry.cells.min_y.max(query.min_y);
+                    if (x, y) == (first_x, first_y) && entry.bounds.collides(&rect) {
+                        visit(entry);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn bounds_of(&self, entity: &Entity) -> Option<Rect> {
+        self.entries
+            .get(&entity_key(entity))
+            .map(|entry| entry.bounds)
+    }
+
+    /// Entities whose bounds overlap *rect*.
+    pub fn query_rect(&self, rect: Rect) -> Vec<Entity> {
+        let mut result = Vec::new();
+        self.for_each_in_rect(rect, |entry| result.push(entry.entity.clone()));
+        result
+    }
+
+    /// Entities overlapping *rect* in the order they were inserted.
+    pub(crate) fn query_rect_in_order(&self, rect: Rect) -> Vec<Entity> {
+        let mut result = Vec::new();
+        self.for_each_in_rect(rect, |entry| {
+            result.push((entry.order, entry.entity.clone()))
+        });
+        result.sort_by_key(|(order, _)| *order);
+        result.into_iter().map(|(_, entity)| entity).collect()
+    }
+
+    /// Entities whose bounds are within *radius* of *center*.
+    pub fn query_radius(&self, center: Vec2, radius: i32) -> Vec<Entity> {
+        let square = Rect::from_center_size(
+            center,
+            Vec2 {
+                x: 2 * radius,
+                y: 2 * radius,
+            },
+        );
+        let mut result = Vec::new();
+        self.for_each_in_rect(square, |entry| {
+            if distance_squared_to_rect(center, entry.bounds) <= radius as i64 * radius as i64 {
+                result.push(entry.entity.clone());
+            }
+        });
+        result
+    }
+
+    /// Entity with bounds closest to *point* for which *filter* returns `true`.
+    /// The distance is 0 inside the bounds. `None` for *max_distance* searches the whole grid.
+    pub fn nearest<F>(
+        &self,
+        point: Vec2,
+        max_distance: Option<i32>,
+        mut filter: F,
+    ) -> Option<Entity>
+    where
+        F: FnMut(&Entity) -> bool,
+    {
+        let limit = match max_distance {
+            Some(max_distance) => max_distance,
+            None => self.farthest_cell_distance(point)?,
+        };
+
+        // grow the search square until something is found; anything closer is inside it
+        let mut radius = self.cell_size.min(limit);
+        loop {
+            let square = Rect::from_center_size(
+                point,
+                Vec2 {
+                    x: 2 * radius,
+                    y: 2 * radius,
+                },
+            );
+            let mut best: Option<(i64, Entity)> = None;
+            self.for_each_in_rect(square, |entry| {
+                let distance = distance_squared_to_rect(point, entry.bounds);
+                if distance <= radius as i64 * radius as i64
+                    && best
+                        .as_ref()
+                        .is_none_or(|(best_distance, _)| distance < *best_distance)
+                    && filter(&entry.entity)
+                {
+                    best = Some((distance, entry.entity.clone()));
+                }
+            });
+            if best.is_some() || radius >= limit {
+                return best.map(|(_, entity)| entity);
+            }
+            radius = radius.saturating_mul(2).min(limit);
+        }
+    }
+
+    /// Distance from *point* to the far corner of the occupied cells.
+    fn farthest_cell_distance(&self, point: Vec2) -> Option<i32> {
+        let mut keys = self.cells.keys();
+        let first = *keys.next()?;
+        let (min, max) = keys.fold((first, first), |(min, max), &(x, y)| {
+            ((min.0.min(x), min.1.min(y)), (max.0.max(x), max.1.max(y)))
+        });
+        let size = self.cell_size as i64;
+        let dx = (point.x as i64 - min.0 as i64 * size)
+            .abs()
+            .max(((max.0 as i64 + 1) * size - point.x as i64).abs());
+        let dy = (point.y as i64 - min.1 as i64 * size)
+            .abs()
+            .max(((max.1 as i64 + 1) * size - point.y as i64).abs());
+        // capped so the search square still fits in a `Rect`
+        Some(
+            ((dx * dx + dy * dy) as f64)
+                .sqrt()
+                .ceil()
+                .min((i32::MAX / 4) as f64) as i32,
+        )
+    }
+
+    /// First entity hit by the segment from *from* to *to*.
+    /// Cells are walked in order along the segment, so far entities are not tested.
+    pub fn raycast(&self, from: Vec2, to: Vec2) -> Option<RayHit> {
+        let (dx, dy) = ((to.x - from.x) as f32, (to.y - from.y) as f32);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length == 0.0 {
+            return None;
+        }
+        let (dir_x, dir_y) = (dx / length, dy / length);
+        let cell_size = self.cell_size as f32;
+
+        // DDA over the cells, see "A Fast Voxel Traversal Algorithm" by Amanatides and Woo
+        let mut cell_x = from.x.div_euclid(self.cell_size);
+        let mut cell_y = from.y.div_euclid(self.cell_size);
+        let end_x = to.x.div_euclid(self.cell_size);
+        let end_y = to.y.div_euclid(self.cell_size);
+        let step_x = if dir_x >= 0.0 { 1 } else { -1 };
+        let step_y = if dir_y >= 0.0 { 1 } else { -1 };
+        let boundary = |cell: i32, step: i32| (cell + (step > 0) as i32) as f32 * cell_size;
+        let mut next_x = if dir_x == 0.0 {
+            f32::INFINITY
+        } else {
+            (boundary(cell_x, step_x) - from.x as f32) / dir_x
+        };
+        let mut next_y = if dir_y == 0.0 {
+            f32::INFINITY
+        } else {
+            (boundary(cell_y, step_y) - from.y as f32) / dir_y
+        };
+        let delta_x = cell_size / dir_x.abs();
+        let delta_y = cell_size / dir_y.abs();
+
+        let mut best: Option<(f32, &Entry)> = None;
+        loop {
+            if let Some(cell) = self.cells.get(&(cell_x, cell_y)) {
+                for key in cell {
+                    let entry = &self.entries[key];
+                    if let Some(distance) =
+                        ray_rect_distance(from, (dir_x, dir_y), length, entry.bounds)
+                    {
+                        if best.is_none_or(|(best_distance, _)| distance < best_distance) {
+                            best = Some((distance, entry));
+                        }
+                    }
+                }
+            }
+            // a hit before the exit of this cell can't be beaten by later cells
+            let cell_exit = next_x.min(next_y);
+            if best.is_some_and(|(distance, _)| distance <= cell_exit)
+                || (cell_x, cell_y) == (end_x, end_y)
+                || cell_exit > length
+            {
+                break;
+            }
+            if next_x < next_y {
+                next_x += delta_x;
+                cell_x += step_x;
+            } else {
+                next_y += delta_y;
+                cell_y += step_y;
+            }
+        }
+
+        best.map(|(distance, entry)| RayHit {
+            entity: entry.entity.clone(),
+            distance,
+            point: Vec2 {
+                x: (from.x as f32 + dir_x * distance).round() as i32,
+                y: (from.y as f32 + dir_y * distance).round() as i32,
+            },
+        })
+    }
+}
+
+/// Spatial queries.
+///
+/// They use the bounds of each entity, or its sprite rect if it has none.
+/// The index follows entities added with `add_entity` right away,
+/// and their movement at the end of every `Scene::update`, whatever moved them.
+impl Scene {
+    pub fn spatial(&self) -> &SpatialGrid {
+        &self.spatial
+    }
+
+    /// Rebuild the index with cells of *cell_size* world units.
+    pub fn set_spatial_cell_size(&mut self, cell_size: i32) {
+        self.spatial = SpatialGrid::new(cell_size);
+        self.refresh_spatial();
+    }
+
+    /// Move every entity to its current bounds in the index.
+    /// Call it after moving many entities from outside if queries must see it at once.
+    pub fn refresh_spatial(&mut self) {
+        self.spatial.refresh(&self.entity_list);
+    }
+
+    /// Move the entities whose bounds changed since the last refresh.
+    /// Only the changed ones are moved between cells.
+    pub(crate) fn refresh_bounds(&mut self) {
+        for entity in self.entity_list.iter() {
+            let bounds = entity_bounds(entity);
+            if self.spatial.bounds_of(entity) != Some(bounds) {
+                self.spatial.update(entity, bounds);
+            }
+        }
+    }
+
+    /// Entities overlapping *rect*.
+    pub fn query_rect(&self, rect: Rect) -> Vec<Entity> {
+        self.spatial.query_rect(rect)
+    }
+
+    /// Entities within *radius* of *center*.
+    pub fn query_radius(&self, center: Vec2, radius: i32) -> Vec<Entity> {
+        self.spatial.query_radius(center, radius)
+    }
+
+    /// Entities within *radius* of the bounds of *entity*, excluding itself.
+    pub fn neighbors(&self, entity: &Entity, radius: i32) -> Vec<Entity> {
+        let Some(bounds) = self.spatial.bounds_of(entity) else {
+            return Vec::new();
+        };
+        let area = Rect {
+            x: bounds.x - radius,
+            y: bounds.y - radius,
+            w: bounds.w + 2 * radius,
+            h: bounds.h + 2 * radius,
+        };
+        let key = entity_key(entity);
+        self.spatial
+            .query_rect(area)
+            .into_iter()
+            .filter(|other| entity_key(other) != key)
+            .collect()
+    }
+
+    /// Entity closest to *point*, only among entities with *tag* if given.
+    pub fn nearest(&self, point: Vec2, tag: Option<&str>) -> Option<Entity> {
+        self.spatial.nearest(point, None, |entity| {
+            tag.is_none_or(|tag| self.has_tag(entity, tag))
+        })
+    }
+
+    /// First entity hit by the segment from *from* to *to*.
+    pub fn raycast(&self, from: Vec2, to: Vec2) -> Option<RayHit> {
+        self.spatial.raycast(from, to)
+    }
+}
+
+/// Rect of *entity* in the index: `bounds()`, or the sprite rect if it has none.
+pub(crate) fn entity_bounds(entity: &Entity) -> Rect {
+    let entity = entity.borrow();
+    entity
+        .bounds()
+        .unwrap_or_else(|| entity.get_draw_info().world_rect)
+}
+
+fn distance_squared_to_rect(point: Vec2, rect: Rect) -> i64 {
+    let top_right = rect.point_right_top();
+    let dx = (rect.x - point.x).max(0).max(point.x - top_right.x) as i64;
+    let dy = (rect.y - point.y).max(0).max(point.y - top_right.y) as i64;
+    dx * dx + dy * dy
+}
+
+/// Distance along the ray to *rect* by the slab method, if it is hit within *length*.
+fn ray_rect_distance(origin: Vec2, dir: (f32, f32), length: f32, rect: Rect) -> Option<f32> {
+    let top_right = rect.point_right_top();
+    let mut t_min = 0.0f32;
+    let mut t_max = length;
+    for (origin, dir, min, max) in [
+        (origin.x as f32, dir.0, rect.x as f32, top_right.x as f32),
+        (origin.y as f32, dir.1, rect.y as f32, top_right.y as f32),
+    ] {
+        if dir == 0.0 {
+            if origin < min || origin > max {
+                return None;
+            }
+            continue;
+        }
+        let t1 = (min - origin) / dir;
+        let t2 = (max - origin) / dir;
+        t_min = t_min.max(t1.min(t2));
+        t_max = t_max.min(t1.max(t2));
+        if t_min > t_max {
+            return None;
+        }
+    }
+    Some(t_min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::entity::{EntityDrawInfo, EntityTrait};
+    use crate::game::tween::Tween;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    struct Dot {
+        pos: Vec2,
+        size: Vec2,
+    }
+
+    impl EntityTrait for Dot {
+        fn pos(&self) -> Vec2 {
+            self.pos
+        }
+
+        fn set_pos(&mut self, new_pos: Vec2) {
+            self.pos = new_pos;
+        }
+
+        fn update(&mut self) {}
+
+        fn get_draw_info(&self) -> EntityDrawInfo {
+            unreachable!("headless entities are never drawn")
+        }
+
+        fn bounds(&self) -> Option<Rect> {
+            Some(Rect::from_center_size(self.pos, self.size))
+        }
+    }
+
+    fn view() -> Rect {
+        Rect::from_center_size(Vec2 { x: 5000, y: 5000 }, Vec2 { x: 100, y: 100 })
+    }
+
+    #[test]
+    fn tweened_entity_is_found() {
+        let mut scene = Scene::new();
+        let dot = Rc::new(RefCell::new(Dot {
+            pos: Vec2 { x: 0, y: 0 },
+            size: Vec2 { x: 16, y: 16 },
+        }));
+        scene.add_entity(dot.clone());
+        scene.add_tween(Tween::position(dot, Vec2 { x: 5000, y: 5000 }, 10));
+
+        assert!(scene.query_rect(view()).is_empty());
+        for _ in 0..20 {
+            scene.update(Duration::from_millis(16));
+        }
+        assert_eq!(scene.query_rect(view()).len(), 1);
+    }
+
+    #[test]
+    fn resized_entity_is_found() {
+        let mut scene = Scene::new();
+        let dot = Rc::new(RefCell::new(Dot {
+            pos: Vec2 { x: 4900, y: 4900 },
+            size: Vec2 { x: 16, y: 16 },
+        }));
+        scene.add_entity(dot.clone());
+        assert!(scene.query_rect(view()).is_empty());
+
+        // grows over the view without moving
+        dot.borrow_mut().size = Vec2 { x: 200, y: 200 };
+        scene.update(Duration::from_millis(16));
+        assert_eq!(scene.query_rect(view()).len(), 1);
+    }
+}
@@ -0,0 +1,503 @@
+use std::f32::consts::PI;
+
+use crate::game::entity::Entity;
+use crate::game::scene::SceneInfo;
+use crate::types::*;
+
+/// Easing curves. See <https://easings.net> for the shapes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Easing {
+    #[default]
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+    ExpoIn,
+    ExpoOut,
+    BackIn,
+    BackOut,
+    ElasticOut,
+    BounceOut,
+}
+
+impl Easing {
+    /// Map progress *t* (0.0 to 1.0) to the eased progress.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::QuadInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+                }
+            }
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::SineIn => 1.0 - (t * PI / 2.0).cos(),
+            Easing::SineOut => (t * PI / 2.0).sin(),
+            Easing::SineInOut => -((t * PI).cos() - 1.0) / 2.0,
+            Easing::ExpoIn => {
+                if t == 0.0 {
+                    0.0
+                } else {
+                    2f32.powf(10.0 * t - 10.0)
+                }
+            }
+            Easing::ExpoOut => {
+                if t == 1.0 {
+                    1.0
+                } else {
+                    1.0 - 2f32.powf(-10.0 * t)
+                }
+            }
+            Easing::BackIn => {
+                let c1 = 1.70158;
+                (c1 + 1.0) * t * t * t - c1 * t * t
+            }
+            Easing::BackOut => {
+                let c1 = 1.70158;
+                1.0 + (c1 + 1.0) * (t - 1.0).powi(3) + c1 * (t - 1.0).powi(2)
+            }
+            Easing::ElasticOut => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else {
+                    2f32.powf(-10.0 * t) * ((t * 10.0 - 0.75) * (2.0 * PI / 3.0)).sin() + 1.0
+                }
+            }
+            Easing::BounceOut => {
+                let (n1, d1) = (7.5625, 2.75);
+                if t < 1.0 / d1 {
+                    n1 * t * t
+                } else if t < 2.0 / d1 {
+                    let t = t - 1.5 / d1;
+                    n1 * t * t + 0.75
+                } else if t < 2.5 / d1 {
+                    let t = t - 2.25 / d1;
+                    n1 * t * t + 0.9375
+                } else {
+                    let t = t - 2.625 / d1;
+                    n1 * t * t + 0.984375
+                }
+            }
+        }
+    }
+}
+
+/// How many times an animation is played.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Repeat {
+    #[default]
+    Once,
+    Times(u32),
+    Forever,
+}
+
+impl Repeat {
+    fn is_done(self, cycles: u32) -> bool {
+        match self {
+            Repeat::Once => cycles >= 1,
+            Repeat::Times(times) => cycles >= times,
+            Repeat::Forever => false,
+        }
+    }
+}
+
+/// Value that can be interpolated by a tween.
+pub trait Tweenable: Copy {
+    fn lerp(self, other: Self, t: f32) -> Self;
+}
+
+impl Tweenable for f32 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Tweenable for i32 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        (self as f32 + (other - self) as f32 * t).round() as i32
+    }
+}
+
+impl Tweenable for Vec2 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Vec2 {
+            x: self.x.lerp(other.x, t),
+            y: self.y.lerp(other.y, t),
+        }
+    }
+}
+
+impl Tweenable for Color {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Color::lerp(self, other, t)
+    }
+}
+
+/// Anything driven by `Scene::update` once per tick.
+pub trait Animation {
+    /// Advance one tick. Returns `true` when the animation is finished.
+    fn update(&mut self, scene_info: &mut SceneInfo) -> bool;
+    /// Rewind to the beginning, so it can be played again.
+    fn reset(&mut self);
+}
+
+type Callback = Box<dyn FnMut(&mut SceneInfo)>;
+type Getter<T> = Box<dyn Fn(&SceneInfo) -> T>;
+type Setter<T> = Box<dyn FnMut(T, &mut SceneInfo)>;
+
+/// Animates a value from `from` to `to` over `duration` ticks.
+pub struct Tween<T: Tweenable> {
+    from: Option<T>,
+    /// reads `from` when the tween starts
+    getter: Option<Getter<T>>,
+    to: T,
+    setter: Setter<T>,
+
+    duration: u32,
+    delay: u32,
+    easing: Easing,
+    repeat: Repeat,
+    yoyo: bool,
+    on_complete: Option<Callback>,
+
+    elapsed: u32,
+    cycles: u32,
+}
+
+impl<T: Tweenable + 'static> Tween<T> {
+    /// Tween from *from* to *to*. *setter* is called with the value every tick.
+    pub fn new<F>(from: T, to: T, duration: u32, setter: F) -> Self
+    where
+        F: FnMut(T, &mut SceneInfo) + 'static,
+    {
+        Self {
+            from: Some(from),
+            getter: None,
+            to,
+            setter: Box::new(setter),
+            duration,
+            delay: 0,
+            easing: Easing::Linear,
+            repeat: Repeat::Once,
+            yoyo: false,
+            on_complete: None,
+            elapsed: 0,
+            cycles: 0,
+        }
+    }
+
+    /// Tween from the value read by *getter* when the tween starts.
+    pub fn from_current<G, F>(getter: G, to: T, duration: u32, setter: F) -> Self
+    where
+        G: Fn(&SceneInfo) -> T + 'static,
+        F: FnMut(T, &mut SceneInfo) + 'static,
+    {
+        Self {
+            from: None,
+            getter: Some(Box::new(getter)),
+            ..Self::new(to, to, duration, setter)
+        }
+    }
+
+    pub fn easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Wait *delay* ticks before the first cycle.
+    pub fn delay(mut self, delay: u32) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Play every other cycle backward.
+    pub fn yoyo(mut self, yoyo: bool) -> Self {
+        self.yoyo = yoyo;
+        self
+    }
+
+    pub fn on_complete<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&mut SceneInfo) + 'static,
+    {
+        self.on_complete = Some(Box::new(callback));
+        self
+    }
+}
+
+impl Tween<Vec2> {
+    /// Move *entity* to *to* in world space.
+    pub fn position(entity: Entity, to: Vec2, duration: u32) -> Self {
+        let target = entity.clone();
+        Self::from_current(
+            move |_| entity.borrow().pos(),
+            to,
+            duration,
+            move |pos, _| target.borrow_mut().set_pos(pos),
+        )
+    }
+
+    /// Move the camera to *to* in world space.
+    pub fn camera_center(to: Vec2, duration: u32) -> Self {
+        Self::from_current(
+            |scene_info| scene_info.camera.position(),
+            to,
+            duration,
+            |center, scene_info| scene_info.camera.set_center(center),
+        )
+    }
+}
+
+impl Tween<f32> {
+    pub fn camera_zoom(to: f32, duration: u32) -> Self {
+        Self::from_current(
+            |scene_info| scene_info.camera.zoom(),
+            to,
+            duration,
+            |zoom, scene_info| scene_info.camera.set_zoom(zoom),
+        )
+    }
+}
+
+impl<T: Tweenable> Animation for Tween<T> {
+    fn update(&mut self, scene_info: &mut SceneInfo) -> bool {
+        if self.elapsed < self.delay {
+            self.elapsed += 1;
+            return false;
+        }
+
+        let from = match (self.from, &self.getter) {
+            (Some(from), _) => from,
+            (None, Some(getter)) => *self.from.insert(getter(scene_info)),
+            (None, None) => self.to,
+        };
+
+        self.elapsed += 1;
+        let tick = self.elapsed - self.delay;
+        let progress = tick as f32 / self.duration.max(1) as f32;
+        let backward = self.yoyo && self.cycles % 2 == 1;
+        let progress = if backward { 1.0 - progress } else { progress };
+        (self.setter)(from.lerp(self.to, self.easing.apply(progress)), scene_info);
+
+        if tick < self.duration {
+            return false;
+        }
+
+        self.cycles += 1;
+        if self.repeat.is_done(self.cycles) {
+            if let Some(on_complete) = &mut self.on_complete {
+                on_complete(scene_info);
+            }
+            true
+        } else {
+            // the delay is only for the first cycle
+            self.elapsed = self.delay;
+            false
+        }
+    }
+
+    fn reset(&mut self) {
+        self.elapsed = 0;
+        self.cycles = 0;
+        if self.getter.is_some() {
+            self.from = None;
+        }
+    }
+}
+
+/// Waits for given ticks. Use it inside `Sequence`.
+pub struct Delay {
+    duration: u32,
+    elapsed: u32,
+}
+
+impl Delay {
+    pub fn new(duration: u32) -> Self {
+        Self {
+            duration,
+            elapsed: 0,
+        }
+    }
+}
+
+impl Animation for Delay {
+    fn update(&mut self, _scene_info: &mut SceneInfo) -> bool {
+        self.elapsed += 1;
+        self.elapsed >= self.duration
+    }
+
+    fn reset(&mut self) {
+        self.elapsed = 0;
+    }
+}
+
+/// Plays animations one after another.
+#[derive(Default)]
+pub struct Sequence {
+    animations: Vec<Box<dyn Animation>>,
+    repeat: Repeat,
+    on_complete: Option<Callback>,
+
+    current: usize,
+    cycles: u32,
+}
+
+impl Sequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn then(mut self, animation: impl Animation + 'static) -> Self {
+        self.animations.push(Box::new(animation));
+        self
+    }
+
+    /// Wait *duration* ticks before the next animation.
+    pub fn wait(self, duration: u32) -> Self {
+        self.then(Delay::new(duration))
+    }
+
+    pub fn repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn on_complete<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&mut SceneInfo) + 'static,
+    {
+        self.on_complete = Some(Box::new(callback));
+        self
+    }
+}
+
+impl Animation for Sequence {
+    fn update(&mut self, scene_info: &mut SceneInfo) -> bool {
+        if let Some(animation) = self.animations.get_mut(self.current) {
+            if animation.update(scene_info) {
+                self.current += 1;
+            }
+        }
+        if self.current < self.animations.len() {
+            return false;
+        }
+
+        self.cycles += 1;
+        if self.repeat.is_done(self.cycles) {
+            if let Some(on_complete) = &mut self.on_complete {
+                on_complete(scene_info);
+            }
+            true
+        } else {
+            self.current = 0;
+            self.animations
+                .iter_mut()
+                .for_each(|animation| animation.reset());
+            false
+        }
+    }
+
+    fn reset(&mut self) {
+        self.current = 0;
+        self.cycles = 0;
+        self.animations
+            .iter_mut()
+            .for_each(|animation| animation.reset());
+    }
+}
+
+/// Plays animations at the same time. Finished when every animation is finished.
+#[derive(Default)]
+pub struct Parallel {
+    animations: Vec<(Box<dyn Animation>, bool)>,
+    repeat: Repeat,
+    on_complete: Option<Callback>,
+
+    cycles: u32,
+}
+
+impl Parallel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, animation: impl Animation + 'static) -> Self {
+        self.animations.push((Box::new(animation), false));
+        self
+    }
+
+    pub fn repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn on_complete<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&mut SceneInfo) + 'static,
+    {
+        self.on_complete = Some(Box::new(callback));
+        self
+    }
+}
+
+impl Animation for Parallel {
+    fn update(&mut self, scene_info: &mut SceneInfo) -> bool {
+        for (animation, finished) in self.animations.iter_mut() {
+            if !*finished {
+                *finished = animation.update(scene_info);
+            }
+        }
+        if !self.animations.iter().all(|(_, finished)| *finished) {
+            return false;
+        }
+
+        self.cycles += 1;
+        if self.repeat.is_done(self.cycles) {
+            if let Some(on_complete) = &mut self.on_complete {
+                on_complete(scene_info);
+            }
+            true
+        } else {
+            self.reset_animations();
+            false
+        }
+    }
+
+    fn reset(&mut self) {
+        self.cycles = 0;
+        self.reset_animations();
+    }
+}
+
+impl Parallel {
+    fn reset_animations(&mut self) {
+        for (animation, finished) in self.animations.iter_mut() {
+            animation.reset();
+            *finished = false;
+        }
+    }
+}
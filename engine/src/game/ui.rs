@@ -1,6 +1,7 @@
 use crate::game::scene::SceneInfo;
 use crate::Renderer;
 
+pub mod progress;
 pub mod text;
 
 pub trait UIElement {
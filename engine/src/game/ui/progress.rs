@@ -0,0 +1,44 @@
+use crate::game::scene::SceneInfo;
+use crate::game::ui::UIElement;
+use crate::render::primitive::Space;
+use crate::Renderer;
+
+use crate::types::*;
+
+/// Bar filled with the progress of an asset group, for loading screens.
+pub struct ProgressBar {
+    pub group: String,
+    /// screen rect of the bar, in pixels
+    pub rect: Rect,
+    pub color: Color,
+    pub background: Color,
+    /// hide the bar once every asset of the group is loaded
+    pub hide_when_done: bool,
+}
+
+impl ProgressBar {
+    pub fn new(group: &str, rect: Rect) -> Self {
+        Self {
+            group: group.to_string(),
+            rect,
+            color: Color::rgb(255, 255, 255),
+            background: Color::rgb(40, 40, 40),
+            hide_when_done: true,
+        }
+    }
+}
+
+impl UIElement for ProgressBar {
+    fn draw(&self, renderer: &mut Renderer, _scene_info: &SceneInfo) {
+        let progress = renderer.texture_manager.progress(&self.group);
+        if self.hide_when_done && progress.is_done() {
+            return;
+        }
+        renderer.fill_rect(Space::Screen, self.rect, self.background);
+        let filled = Rect {
+            w: (self.rect.w as f32 * progress.fraction()) as i32,
+            ..self.rect
+        };
+        renderer.fill_rect(Space::Screen, filled, self.color);
+    }
+}
@@ -1,6 +1,10 @@
+use std::cell::{Cell, RefCell};
+
 use crate::game::scene::SceneInfo;
 use crate::game::ui::UIElement;
+use crate::locale::{Localization, LocalizedText};
 use crate::render::font::Font;
+use crate::render::rich_text::{self, RichFont, RichText};
 use crate::Renderer;
 
 use crate::types::*;
@@ -8,8 +12,21 @@ use crate::types::*;
 pub struct TextElement {
     font: Font,
     pub text: String,
+    /// shown instead of *text* when set, in the current language of *localization*
+    pub localized: Option<LocalizedText>,
+    localization: Option<Localization>,
     pub pos: Vec2,
     pub scale: Vec2,
+    /// glyphs revealed per frame. `None` shows the whole text at once.
+    /// only for markup text.
+    pub typewriter: Option<f32>,
+    /// fonts of markup text. the text is drawn as it is without them.
+    rich_font: Option<RichFont>,
+    /// source and parsed markup, parsed again when the text changes
+    parsed: RefCell<Option<(String, RichText)>>,
+    /// frame the typewriter started from
+    reveal_start: Cell<Option<usize>>,
+    revealed: Cell<bool>,
 }
 
 impl TextElement {
@@ -17,19 +34,103 @@ impl TextElement {
         Self {
             font,
             text: String::from("Hello world!"),
+            localized: None,
+            localization: None,
             pos: Vec2 { x: 0, y: 0 },
             scale: Vec2 { x: 100, y: 100 },
+            typewriter: None,
+            rich_font: None,
+            parsed: RefCell::new(None),
+            reveal_start: Cell::new(None),
+            revealed: Cell::new(false),
+        }
+    }
+
+    /// Text element showing *text* from *localization*.
+    /// It follows language changes of *localization*.
+    pub fn localized(font: Font, localization: &Localization, text: LocalizedText) -> Self {
+        Self {
+            localized: Some(text),
+            localization: Some(localization.clone()),
+            ..Self::new(font)
+        }
+    }
+
+    /// Parse the text as `RichText` markup and draw it with *rich_font*.
+    pub fn markup(mut self, rich_font: RichFont) -> Self {
+        self.rich_font = Some(rich_font);
+        self
+    }
+
+    /// Reveal *glyphs_per_frame* glyphs of markup text every frame.
+    /// It starts over whenever the text changes.
+    pub fn typewriter(mut self, glyphs_per_frame: f32) -> Self {
+        self.typewriter = Some(glyphs_per_frame);
+        self
+    }
+
+    /// Start revealing the text again from the next frame.
+    pub fn restart_reveal(&self) {
+        self.reveal_start.set(None);
+        self.revealed.set(false);
+    }
+
+    /// Show the rest of the text being revealed.
+    pub fn skip_reveal(&self) {
+        self.revealed.set(true);
+    }
+
+    /// Whether the whole text is shown.
+    pub fn is_revealed(&self) -> bool {
+        self.typewriter.is_none() || self.revealed.get()
+    }
+
+    /// Text drawn in the current frame.
+    /// For markup text, the arguments of the localized text are escaped, so they are never parsed as tags.
+    pub fn displayed_text(&self) -> String {
+        match (&self.localized, &self.localization) {
+            (Some(text), Some(localization)) if self.rich_font.is_some() => {
+                let mut escaped = text.clone();
+                for (_, value) in escaped.args.iter_mut() {
+                    *value = rich_text::escape(value);
+                }
+                escaped.resolve(localization)
+            }
+            (Some(text), Some(localization)) => text.resolve(localization),
+            _ => self.text.clone(),
         }
     }
 }
 
 impl UIElement for TextElement {
     fn draw(&self, renderer: &mut Renderer, scene_info: &SceneInfo) {
-        self.font.draw(
-            &mut renderer.canvas,
-            self.text.as_str(),
-            self.pos,
-            self.scale,
-        );
+        let text = self.displayed_text();
+        let Some(rich_font) = &self.rich_font else {
+            self.font
+                .draw(&mut renderer.canvas, text.as_str(), self.pos, self.scale);
+            return;
+        };
+
+        let mut parsed = self.parsed.borrow_mut();
+        if parsed.as_ref().is_none_or(|(source, _)| *source != text) {
+            *parsed = Some((text.clone(), RichText::parse(&text)));
+            self.restart_reveal();
+        }
+        let rich_text = &parsed.as_ref().unwrap().1;
+
+        let frame = renderer.render_info.frame;
+        let start = self.reveal_start.get().unwrap_or(frame);
+        self.reveal_start.set(Some(start));
+        let visible = match self.typewriter {
+            Some(speed) if !self.revealed.get() => {
+                let visible = ((frame - start) as f32 * speed) as usize;
+                if visible >= rich_text.len() {
+                    self.revealed.set(true);
+                }
+                visible
+            }
+            _ => usize::MAX,
+        };
+        rich_text.draw(renderer, rich_font, self.pos, self.scale, visible);
     }
 }
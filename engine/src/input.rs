@@ -0,0 +1,294 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use sdl2::controller::GameController;
+use sdl2::event::Event;
+use sdl2::rwops::RWops;
+use sdl2::GameControllerSubsystem;
+
+pub use sdl2::controller::{Axis, Button};
+
+use crate::error::Error;
+
+/// number of player slots controllers are assigned to
+pub const MAX_PLAYERS: usize = 4;
+const AXIS_COUNT: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stick {
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GamepadEventKind {
+    Connected,
+    Disconnected,
+    ButtonDown(Button),
+    ButtonUp(Button),
+    /// axis value after the dead zone, -1.0 to 1.0
+    Axis(Axis, f32),
+}
+
+/// Gamepad input of this frame.
+/// *player* is `None` for controllers not assigned to a player.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GamepadEvent {
+    pub player: Option<usize>,
+    pub kind: GamepadEventKind,
+}
+
+struct Gamepad {
+    controller: GameController,
+    buttons: HashSet<Button>,
+    axes: [i16; AXIS_COUNT],
+}
+
+/// Connected game controllers and their assignment to players.
+///
+/// Controllers are opened when plugged in and given the first free player slot.
+/// Stick values have y-axis going up, like world space.
+pub struct Gamepads {
+    subsystem: GameControllerSubsystem,
+    gamepads: Vec<Gamepad>,
+    /// instance id of the controller of each player
+    players: [Option<u32>; MAX_PLAYERS],
+    /// radial dead zone of sticks and triggers, 0.0 to 1.0
+    deadzone: f32,
+    events: Vec<GamepadEvent>,
+}
+
+impl Gamepads {
+    pub(crate) fn new(subsystem: GameControllerSubsystem) -> Self {
+        Self {
+            subsystem,
+            gamepads: Vec::new(),
+            players: [None; MAX_PLAYERS],
+            deadzone: 0.2,
+            events: Vec::new(),
+        }
+    }
+
+    /// Load controller mappings in the `gamecontrollerdb.txt` format, such as a file read from the `Vfs`.
+    /// Returns the number of mappings added.
+    pub fn load_mappings(&self, mappings: &[u8]) -> Result<usize, Error> {
+        let rw = RWops::from_bytes(mappings).map_err(|_| Error::FileReadFailure)?;
+        self.subsystem
+            .load_mappings_from_rw(rw)
+            .map(|count| count as usize)
+            .map_err(|_| Error::FileReadFailure)
+    }
+
+    /// Stick and trigger values below *deadzone* read as zero.
+    pub fn set_deadzone(&mut self, deadzone: f32) {
+        self.deadzone = deadzone.clamp(0.0, 0.99);
+    }
+
+    /// Events received this frame.
+    pub fn events(&self) -> &[GamepadEvent] {
+        &self.events
+    }
+
+    /// Clear the events of the last frame.
+    pub(crate) fn begin_frame(&mut self) {
+        self.events.clear();
+    }
+
+    /// Update controller state with *event*. Returns `true` if it was a controller event.
+    pub(crate) fn handle_event(&mut self, event: &Event) -> bool {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                match self.subsystem.open(which) {
+                    Ok(controller) => self.connect(controller),
+                    Err(err) => println!("failed to open controller {which} : {err}"),
+                }
+                true
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                let player = self.player_of(which);
+                self.gamepads
+                    .retain(|gamepad| gamepad.controller.instance_id() != which);
+                if let Some(player) = player {
+                    self.players[player] = None;
+                }
+                self.events.push(GamepadEvent {
+                    player,
+                    kind: GamepadEventKind::Disconnected,
+                });
+                true
+            }
+            Event::ControllerButtonDown { which, button, .. } => {
+                if let Some(gamepad) = self.gamepad_mut(which) {
+                    gamepad.buttons.insert(button);
+                }
+                self.push_event(which, GamepadEventKind::ButtonDown(button));
+                true
+            }
+            Event::ControllerButtonUp { which, button, .. } => {
+                if let Some(gamepad) = self.gamepad_mut(which) {
+                    gamepad.buttons.remove(&button);
+                }
+                self.push_event(which, GamepadEventKind::ButtonUp(button));
+                true
+            }
+            Event::ControllerAxisMotion {
+                which, axis, value, ..
+            } => {
+                if let Some(gamepad) = self.gamepad_mut(which) {
+                    gamepad.axes[axis as usize] = value;
+                }
+                let value = self.apply_deadzone(normalize(value));
+                self.push_event(which, GamepadEventKind::Axis(axis, value));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn connect(&mut self, controller: GameController) {
+        let instance_id = controller.instance_id();
+        if self.gamepad_mut(instance_id).is_some() {
+            return;
+        }
+        println!("controller connected : {}", controller.name());
+
+        let player = self.players.iter().position(Option::is_none);
+        if let Some(player) = player {
+            self.players[player] = Some(instance_id);
+        }
+        self.gamepads.push(Gamepad {
+            controller,
+            buttons: HashSet::new(),
+            axes: [0; AXIS_COUNT],
+        });
+        self.events.push(GamepadEvent {
+            player,
+            kind: GamepadEventKind::Connected,
+        });
+    }
+
+    /// Give the controller with *instance_id* to *player*.
+    /// The previous controller of the player becomes unassigned.
+    /// *player* must be less than `MAX_PLAYERS`.
+    pub fn assign(&mut self, player: usize, instance_id: u32) -> Result<(), Error> {
+        if player >= MAX_PLAYERS {
+            return Err(Error::InvalidPlayer(player));
+        }
+        for slot in self.players.iter_mut() {
+            if *slot == Some(instance_id) {
+                *slot = None;
+            }
+        }
+        self.players[player] = Some(instance_id);
+        Ok(())
+    }
+
+    /// Instance ids and names of the connected controllers.
+    pub fn connected(&self) -> Vec<(u32, String)> {
+        self.gamepads
+            .iter()
+            .map(|gamepad| {
+                let controller = &gamepad.controller;
+                (controller.instance_id(), controller.name())
+            })
+            .collect()
+    }
+
+    pub fn player_of(&self, instance_id: u32) -> Option<usize> {
+        self.players
+            .iter()
+            .position(|slot| *slot == Some(instance_id))
+    }
+
+    pub fn is_connected(&self, player: usize) -> bool {
+        self.gamepad(player).is_some()
+    }
+
+    pub fn is_button_down(&self, player: usize, button: Button) -> bool {
+        self.gamepad(player)
+            .is_some_and(|gamepad| gamepad.buttons.contains(&button))
+    }
+
+    /// Buttons held by *player*.
+    pub fn buttons_down(&self, player: usize) -> Vec<Button> {
+        self.gamepad(player)
+            .map(|gamepad| gamepad.buttons.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Value of *axis* after the dead zone. Sticks are -1.0 to 1.0, triggers 0.0 to 1.0.
+    /// Stick y-axis goes down, as reported by SDL.
+    pub fn axis(&self, player: usize, axis: Axis) -> f32 {
+        self.gamepad(player).map_or(0.0, |gamepad| {
+            self.apply_deadzone(normalize(gamepad.axes[axis as usize]))
+        })
+    }
+
+    /// Direction of *stick* with y-axis going up. The length is at most 1.0.
+    pub fn stick(&self, player: usize, stick: Stick) -> (f32, f32) {
+        let Some(gamepad) = self.gamepad(player) else {
+            return (0.0, 0.0);
+        };
+        let (axis_x, axis_y) = match stick {
+            Stick::Left => (Axis::LeftX, Axis::LeftY),
+            Stick::Right => (Axis::RightX, Axis::RightY),
+        };
+        let x = normalize(gamepad.axes[axis_x as usize]);
+        let y = -normalize(gamepad.axes[axis_y as usize]);
+
+        // radial dead zone, rescaled so the output starts from 0.0 at the edge
+        let length = (x * x + y * y).sqrt();
+        if length <= self.deadzone {
+            return (0.0, 0.0);
+        }
+        let scaled = ((length - self.deadzone) / (1.0 - self.deadzone)).min(1.0);
+        (x / length * scaled, y / length * scaled)
+    }
+
+    /// Vibrate the controller of *player*. Strengths are 0.0 to 1.0.
+    /// Returns `false` if the controller does not support rumble.
+    pub fn rumble(&mut self, player: usize, low: f32, high: f32, duration: Duration) -> bool {
+        let Some(instance_id) = self.players.get(player).copied().flatten() else {
+            return false;
+        };
+        let Some(gamepad) = self.gamepad_mut(instance_id) else {
+            return false;
+        };
+        let strength = |value: f32| (value.clamp(0.0, 1.0) * u16::MAX as f32) as u16;
+        gamepad
+            .controller
+            .set_rumble(strength(low), strength(high), duration.as_millis() as u32)
+            .is_ok()
+    }
+
+    fn gamepad(&self, player: usize) -> Option<&Gamepad> {
+        let instance_id = self.players.get(player).copied().flatten()?;
+        self.gamepads
+            .iter()
+            .find(|gamepad| gamepad.controller.instance_id() == instance_id)
+    }
+
+    fn gamepad_mut(&mut self, instance_id: u32) -> Option<&mut Gamepad> {
+        self.gamepads
+            .iter_mut()
+            .find(|gamepad| gamepad.controller.instance_id() == instance_id)
+    }
+
+    fn push_event(&mut self, instance_id: u32, kind: GamepadEventKind) {
+        let player = self.player_of(instance_id);
+        self.events.push(GamepadEvent { player, kind });
+    }
+
+    fn apply_deadzone(&self, value: f32) -> f32 {
+        if value.abs() <= self.deadzone {
+            0.0
+        } else {
+            value.signum() * (value.abs() - self.deadzone) / (1.0 - self.deadzone)
+        }
+    }
+}
+
+/// raw axis value to -1.0 to 1.0
+fn normalize(value: i16) -> f32 {
+    (value as f32 / i16::MAX as f32).max(-1.0)
+}
@@ -1,22 +1,47 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 
+pub use config::{EngineConfig, WindowMode};
+use debug::DebugOverlay;
 pub use error::Error;
 use game::scene::Scene;
+use input::{Gamepads, Stick};
+use locale::Localization;
+use render::manager::TextureHandle;
 use render::texture::Texture;
 use render::Renderer;
+use replay::{InputFrame, Recording};
 use types::*;
+use vfs::Vfs;
 
+mod config;
+pub mod debug;
 mod error;
 pub mod game;
-mod render;
+pub mod input;
+pub mod locale;
+pub mod random;
+pub mod render;
+pub mod replay;
 pub mod types;
+pub mod vfs;
+
+/// virtual directory of the string tables, `<language>.json`
+const LOCALE_DIRECTORY: &str = "assets/locale";
+/// community controller mappings, https://github.com/mdqinc/SDL_GameControllerDB
+const CONTROLLER_DB_PATH: &str = "assets/gamecontrollerdb.txt";
 
 /// Game engine.
 pub struct Engine {
     pub renderer: render::Renderer,
+    pub debug: DebugOverlay,
+    pub gamepads: Gamepads,
+    vfs: Arc<Vfs>,
+    localization: Localization,
+    config: EngineConfig,
 
     // TODO: change current_scene to reference
     current_scene: usize,
@@ -26,11 +51,17 @@ pub struct Engine {
 
     start_time: std::time::Instant,
     last_elapsed: std::time::Duration,
+
+    seed: u64,
+    /// input being recorded and the file it is saved to
+    recording: Option<(PathBuf, Recording)>,
+    /// input being replayed and the next frame
+    replay: Option<(Recording, usize)>,
 }
 
 impl Engine {
     /// Creates new window and initialize everything.
-    pub fn new() -> Result<Self, Error> {
+    pub fn new(config: EngineConfig) -> Result<Self, Error> {
         let sdl_context = sdl2::init()
             .map_err(|err| Error::InitFailure(format!("sdl2 initialization failed : {err}")))?;
 
@@ -41,23 +72,52 @@ impl Engine {
             Error::InitFailure(format!("audio subsystem initialization failed : {err}"))
         })?;
 
-        let renderer = render::Renderer::new(&video_subsystem);
+        let controller_subsystem = sdl_context.game_controller().map_err(|err| {
+            Error::InitFailure(format!(
+                "game controller subsystem initialization failed : {err}"
+            ))
+        })?;
+
+        let vfs = Arc::new(Vfs::with_default_mounts());
+        let localization =
+            locale::load_localization(vfs.clone(), LOCALE_DIRECTORY, &config.language);
+        let renderer = render::Renderer::new(&video_subsystem, &config, vfs.clone());
         let event_pump = sdl_context.event_pump().unwrap();
 
+        let gamepads = Gamepads::new(controller_subsystem);
+        if vfs.exists(CONTROLLER_DB_PATH) {
+            let loaded = vfs
+                .read(CONTROLLER_DB_PATH)
+                .and_then(|mappings| gamepads.load_mappings(&mappings));
+            if let Err(err) = loaded {
+                println!("failed to load controller mappings : {err:?}");
+            }
+        }
+
         Ok(Self {
             renderer,
+            debug: DebugOverlay::default(),
+            gamepads,
+            vfs,
+            localization,
+            config,
             current_scene: 0,
             scenes: Vec::new(),
             event_pump,
             start_time: std::time::Instant::now(),
             last_elapsed: std::time::Duration::new(0, 0),
+            seed: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |time| time.as_nanos() as u64),
+            recording: None,
+            replay: None,
         })
     }
 
     /// update the current scene
-    fn update(&mut self) {
+    fn update(&mut self, delta: std::time::Duration) {
         if let Some(scene) = self.scenes.get_mut(self.current_scene) {
-            scene.update();
+            scene.update(delta);
         } else {
             panic!("no scene");
         }
@@ -66,101 +126,247 @@ impl Engine {
     // render the current scene
     fn render(&mut self) {
         if let Some(scene) = self.scenes.get(self.current_scene) {
-            self.renderer.render(scene);
+            self.renderer.render(scene, &self.debug);
         } else {
             panic!("no scene");
         }
     }
 
-    /// loads texture named *name* from *path*.
+    /// loads texture named *name* from the virtual *path*, such as `assets/human.json`.
     /// when file extension is `.json`, the JSON metadata is loaded together.
-    pub fn load_texture(&mut self, name: &'static str, path: &Path) {
+    pub fn load_texture(&mut self, name: &str, path: &str) {
         self.renderer.texture_manager.load(name, path).unwrap();
     }
 
+    /// loads texture named *name* from the virtual *path* in the background.
+    /// the handle resolves to the placeholder texture until it is loaded.
+    pub fn load_texture_async(&mut self, name: &str, path: &str) -> TextureHandle {
+        self.renderer.texture_manager.load_async(name, path)
+    }
+
     /// Get Texture by name.
-    /// Texture should have been loaded.
-    pub fn get_texture(&mut self, name: &'static str) -> Texture {
+    /// Texture should have been loaded, or be loading in the background.
+    pub fn get_texture(&mut self, name: &str) -> Texture {
         self.renderer.texture_manager.get(name)
     }
 
+    /// File system the assets are loaded from. Mount packs or directories to it.
+    pub fn vfs(&self) -> &Arc<Vfs> {
+        &self.vfs
+    }
+
+    /// String tables of the current language, loaded from `assets/locale`.
+    /// Give it to `TextElement::localized` to follow language changes.
+    pub fn localization(&self) -> &Localization {
+        &self.localization
+    }
+
+    /// Switch the language of the string tables and save it to the settings file.
+    pub fn set_language(&mut self, language: &str) -> Result<(), Error> {
+        self.localization.set_language(language)?;
+        self.config.language = language.to_string();
+        self.config.persist()
+    }
+
+    pub fn config(&self) -> &EngineConfig {
+        &self.config
+    }
+
+    /// Change window mode and save it to the settings file.
+    pub fn set_window_mode(&mut self, mode: WindowMode) -> Result<(), Error> {
+        self.renderer.set_window_mode(mode)?;
+        self.config.window_mode = mode;
+        self.config.persist()
+    }
+
+    /// Switch between windowed and fullscreen.
+    pub fn toggle_fullscreen(&mut self) -> Result<(), Error> {
+        let mode = match self.config.window_mode {
+            WindowMode::Windowed => WindowMode::Fullscreen,
+            WindowMode::Fullscreen | WindowMode::Borderless => WindowMode::Windowed,
+        };
+        self.set_window_mode(mode)
+    }
+
+    /// Change window size and save it to the settings file.
+    pub fn set_resolution(&mut self, size: Vec2) -> Result<(), Error> {
+        self.renderer.set_window_size(size)?;
+        self.config.size = size;
+        self.config.persist()
+    }
+
+    /// Limit frame rate and save it to the settings file. `None` for unlimited.
+    pub fn set_frame_rate_cap(&mut self, frame_rate_cap: Option<u32>) -> Result<(), Error> {
+        self.config.frame_rate_cap = frame_rate_cap;
+        self.config.persist()
+    }
+
+    /// Seed for the random number generators of the game.
+    /// A replay restores the seed it was recorded with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// Record input from the next frame. It is saved to *path* when the main loop ends.
+    pub fn record(&mut self, path: &Path) {
+        self.recording = Some((path.to_path_buf(), Recording::new(self.seed)));
+    }
+
+    /// Play input recorded at *path* instead of the live input. Live input resumes at the end.
+    /// Call it before building scenes, so they use the recorded seed.
+    pub fn replay(&mut self, path: &Path) -> Result<(), Error> {
+        let recording = Recording::load(path)?;
+        self.seed = recording.seed;
+        self.replay = Some((recording, 0));
+        Ok(())
+    }
+
+    fn finish_recording(&mut self) {
+        if let Some((path, recording)) = self.recording.take() {
+            match recording.save(&path) {
+                Ok(()) => println!(
+                    "saved {} frames to {}",
+                    recording.frames.len(),
+                    path.display()
+                ),
+                Err(err) => println!("failed to save recording : {err:?}"),
+            }
+        }
+    }
+
     pub fn main_loop(mut self) {
         loop {
-            let curr_scene = self.scenes.get_mut(self.current_scene).unwrap();
+            let frame_start = std::time::Instant::now();
+
+            let mut input = InputFrame::default();
+            let mut quit = false;
+            let mut toggle_fullscreen = false;
             // TODO: event handler pattern
+            self.gamepads.begin_frame();
             for event in self.event_pump.poll_iter() {
+                if self.gamepads.handle_event(&event) {
+                    continue;
+                }
                 match event {
-                    Event::Quit { .. } => return,
+                    Event::Quit { .. } => quit = true,
                     Event::Window { win_event, .. } => match win_event {
                         sdl2::event::WindowEvent::Resized(x, y) => {
                             println!("Screen resized: ({x},{y})");
-                            self.renderer.set_screen_size(Vec2 { x, y });
+                            self.renderer.update_screen_size();
                         }
                         _ => (),
                     },
-                    Event::KeyDown { keycode, .. } => {
-                        if let Some(keycode) = keycode {
-                            match keycode {
-                                Keycode::Space => {
-                                    curr_scene.toggle_camera_attachment();
-                                }
-                                Keycode::Escape => {
-                                    return;
-                                }
-                                _ => (),
+                    Event::KeyDown {
+                        keycode: Some(keycode),
+                        repeat,
+                        ..
+                    } => {
+                        if !repeat {
+                            input.key_events.push((keycode.name(), true));
+                        }
+                        match keycode {
+                            Keycode::Escape => {
+                                quit = true;
+                            }
+                            Keycode::F3 => {
+                                self.debug.toggle();
+                            }
+                            Keycode::F4 => {
+                                self.debug.toggle_bounds();
+                            }
+                            Keycode::F11 => {
+                                toggle_fullscreen = true;
                             }
+                            _ => (),
                         }
                     }
+                    Event::KeyUp {
+                        keycode: Some(keycode),
+                        ..
+                    } => {
+                        input.key_events.push((keycode.name(), false));
+                    }
                     Event::MouseWheel { precise_y, .. } => {
-                        curr_scene.add_zoom(precise_y);
+                        input.mouse_wheel += precise_y;
                     }
                     Event::MouseButtonDown { .. } => {}
                     _ => {}
                 }
             }
+            if quit {
+                break;
+            }
 
-            for keycode in self
+            input.held_keys = self
                 .event_pump
                 .keyboard_state()
                 .pressed_scancodes()
                 .filter_map(Keycode::from_scancode)
-            {
-                match keycode {
-                    Keycode::W => {
-                        curr_scene.set_position(curr_scene.get_position() + Vec2 { x: 0, y: 10 })
-                    }
-                    Keycode::A => {
-                        curr_scene.set_position(curr_scene.get_position() + Vec2 { x: -10, y: 0 })
-                    }
-                    Keycode::S => {
-                        curr_scene.set_position(curr_scene.get_position() + Vec2 { x: 0, y: -10 })
-                    }
-                    Keycode::D => {
-                        curr_scene.set_position(curr_scene.get_position() + Vec2 { x: 10, y: 0 })
-                    }
-                    _ => (),
+                .map(|keycode| keycode.name())
+                .collect();
+            input.stick = self.gamepads.stick(0, Stick::Left);
+            input.buttons = self
+                .gamepads
+                .buttons_down(0)
+                .into_iter()
+                .map(|button| button.string())
+                .collect();
+
+            if toggle_fullscreen {
+                if let Err(err) = self.toggle_fullscreen() {
+                    println!("toggle fullscreen failed : {err:?}");
                 }
             }
 
+            // time since the last update
+            let elapsed = self.start_time.elapsed();
+            input.delta = elapsed - self.last_elapsed;
+            self.last_elapsed = elapsed;
+            self.debug.record_frame(input.delta);
+
+            // recorded input replaces the live input, including the frame time
+            if let Some((recording, next)) = self.replay.as_mut() {
+                if let Some(frame) = recording.frames.get(*next) {
+                    input = frame.clone();
+                    *next += 1;
+                } else {
+                    println!("replay finished");
+                    self.replay = None;
+                }
+            }
+            if let Some((_, recording)) = self.recording.as_mut() {
+                recording.frames.push(input.clone());
+            }
+
+            // upload textures loaded in the background
+            self.renderer.texture_manager.poll();
+
             // update game
-            self.update();
+            let curr_scene = self.scenes.get_mut(self.current_scene).expect("no scene");
+            replay::apply_input(curr_scene, &input);
+            self.update(input.delta);
 
             // render the scene
             self.render();
 
-            let elapsed = self.start_time.elapsed();
-            let fps = 1f32 / (elapsed - self.last_elapsed).as_secs_f32();
-            self.last_elapsed = elapsed;
-            // self.renderer.font.draw(
-            //     &mut canvas,
-            //     format!("{fps:.0} FPS").as_str(),
-            //     Vec2 { x: 0, y: 0 },
-            //     Vec2 { x: 32, y: 64 },
-            // );
+            // frame rate cap
+            if let Some(frame_rate_cap) = self.config.frame_rate_cap.filter(|cap| *cap > 0) {
+                let frame_time = std::time::Duration::from_secs_f64(1.0 / frame_rate_cap as f64);
+                if let Some(remaining) = frame_time.checked_sub(frame_start.elapsed()) {
+                    std::thread::sleep(remaining);
+                }
+            }
         }
+        self.finish_recording();
     }
 
-    pub fn add_scene(&mut self, scene: Scene) {
+    /// Add *scene*, seeding its random streams with the engine seed.
+    pub fn add_scene(&mut self, mut scene: Scene) {
+        scene.set_seed(self.seed);
         self.scenes.push(scene)
     }
 }
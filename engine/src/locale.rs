@@ -0,0 +1,347 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::vfs::{self, Vfs};
+
+/// language used for ids missing from the current table
+pub const FALLBACK_LANGUAGE: &str = "en";
+
+/// Entry of a string table.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Entry {
+    Text(String),
+    /// text for each plural category, `zero`, `one`, `two`, `few`, `many` and `other`.
+    /// `other` is used for missing categories.
+    Plural(HashMap<String, String>),
+}
+
+/// Strings of a language by id. The JSON file is a map of id to entry:
+/// ```json
+/// {
+///     "greeting": "Hello {name}!",
+///     "apples": { "one": "{count} apple", "other": "{count} apples" }
+/// }
+/// ```
+/// `{name}` is replaced by the argument *name*, `{{` and `}}` are literal braces.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(transparent)]
+pub struct StringTable {
+    entries: HashMap<String, Entry>,
+}
+
+impl StringTable {
+    pub fn load(vfs: &Vfs, path: &str) -> Result<Self, Error> {
+        serde_json::from_str(&vfs.read_to_string(path)?).map_err(|_| Error::JSONParseFailure)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Entry> {
+        self.entries.get(id)
+    }
+}
+
+/// Shared localization, switched at runtime with `set_language`.
+/// Text elements holding it show the new language from the next frame.
+pub type Localization = Rc<LocalizationInner>;
+
+/// String tables loaded from `<directory>/<language>.json`.
+pub struct LocalizationInner {
+    vfs: Arc<Vfs>,
+    directory: String,
+    language: RefCell<String>,
+    table: RefCell<StringTable>,
+    /// table of `FALLBACK_LANGUAGE`
+    fallback: StringTable,
+}
+
+/// Create localization reading tables from the virtual *directory*, such as `assets/locale`.
+/// A missing table is reported and left empty, so ids are shown as they are.
+pub fn load_localization(vfs: Arc<Vfs>, directory: &str, language: &str) -> Localization {
+    let load = |language: &str| {
+        let path = vfs::join(directory, &format!("{language}.json"));
+        StringTable::load(&vfs, &path)
+            .map_err(|err| println!("failed to load string table {path} : {err:?}"))
+            .unwrap_or_default()
+    };
+    let fallback = if vfs.exists(&vfs::join(directory, &format!("{FALLBACK_LANGUAGE}.json"))) {
+        load(FALLBACK_LANGUAGE)
+    } else {
+        StringTable::default()
+    };
+    let table = if language == FALLBACK_LANGUAGE {
+        fallback.clone()
+    } else {
+        load(language)
+    };
+    Rc::new(LocalizationInner {
+        directory: vfs::normalize(directory),
+        vfs,
+        language: RefCell::new(language.to_string()),
+        table: RefCell::new(table),
+        fallback,
+    })
+}
+
+impl LocalizationInner {
+    pub fn language(&self) -> String {
+        self.language.borrow().clone()
+    }
+
+    /// Languages that have a string table, sorted.
+    pub fn languages(&self) -> Vec<String> {
+        let prefix = format!("{}/", self.directory);
+        self.vfs
+            .files()
+            .iter()
+            .filter_map(|file| file.strip_prefix(&prefix)?.strip_suffix(".json"))
+            .filter(|language| !language.contains('/'))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Switch to *language*. The current language stays if its table fails to load.
+    pub fn set_language(&self, language: &str) -> Result<(), Error> {
+        let path = vfs::join(&self.directory, &format!("{language}.json"));
+        let table = StringTable::load(&self.vfs, &path)?;
+        *self.table.borrow_mut() = table;
+        *self.language.borrow_mut() = language.to_string();
+        Ok(())
+    }
+
+    /// Text of *id*, or *id* itself when neither the current nor the fallback table has it.
+    pub fn text(&self, id: &str) -> String {
+        self.format(id, &[])
+    }
+
+    /// Text of *id* with `{name}` replaced by the value of *name* in *args*.
+    pub fn format(&self, id: &str, args: &[(&str, &str)]) -> String {
+        self.resolve(id, None, args)
+    }
+
+    /// Text of *id* in the plural category of *count*, with `{count}` and *args* replaced.
+    pub fn plural(&self, id: &str, count: i64, args: &[(&str, &str)]) -> String {
+        self.resolve(id, Some(count), args)
+    }
+
+    fn resolve(&self, id: &str, count: Option<i64>, args: &[(&str, &str)]) -> String {
+        let table = self.table.borrow();
+        let entry = table.get(id).or_else(|| self.fallback.get(id));
+        let template = match entry {
+            None => return id.to_string(),
+            Some(Entry::Text(text)) => text.as_str(),
+            Some(Entry::Plural(forms)) => {
+                let category = match count {
+                    Some(count) => plural_category(&self.language.borrow(), count),
+                    None => "other",
+                };
+                match forms.get(category).or_else(|| forms.get("other")) {
+                    Some(text) => text.as_str(),
+                    None => return id.to_string(),
+                }
+            }
+        };
+
+        let count = count.map(|count| count.to_string());
+        let mut all_args = args.to_vec();
+        if let Some(count) = &count {
+            all_args.push(("count", count.as_str()));
+        }
+        substitute(template, &all_args)
+    }
+}
+
+/// Replace `{name}` in *template* with the value of *name* in *args*.
+/// Unknown names are kept as they are.
+pub fn substitute(template: &str, args: &[(&str, &str)]) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(idx) = rest.find(['{', '}']) {
+        result.push_str(&rest[..idx]);
+        let tail = &rest[idx..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            result.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        if tail.starts_with('{') {
+            if let Some(end) = tail.find('}') {
+                let name = &tail[1..end];
+                match args.iter().find(|(arg, _)| *arg == name) {
+                    Some((_, value)) => result.push_str(value),
+                    None => result.push_str(&tail[..=end]),
+                }
+                rest = &tail[end + 1..];
+                continue;
+            }
+        }
+        result.push_str(&tail[..1]);
+        rest = &tail[1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// CLDR plural category of *count* in *language*, for the languages the rules are known.
+/// Other languages use the English rule.
+pub fn plural_category(language: &str, count: i64) -> &'static str {
+    let primary = language.split(['-', '_']).next().unwrap_or(language);
+    let n = count.unsigned_abs();
+    let (n10, n100) = (n % 10, n % 100);
+    match primary {
+        "ja" | "ko" | "zh" | "th" | "vi" | "id" | "ms" => "other",
+        "fr" => {
+            if n <= 1 {
+                "one"
+            } else {
+                "other"
+            }
+        }
+        "ru" | "uk" | "be" => {
+            if n10 == 1 && n100 != 11 {
+                "one"
+            } else if (2..=4).contains(&n10) && !(12..=14).contains(&n100) {
+                "few"
+            } else {
+                "many"
+            }
+        }
+        "pl" => {
+            if n == 1 {
+                "one"
+            } else if (2..=4).contains(&n10) && !(12..=14).contains(&n100) {
+                "few"
+            } else {
+                "many"
+            }
+        }
+        "cs" | "sk" => match n {
+            1 => "one",
+            2..=4 => "few",
+            _ => "other",
+        },
+        "ar" => match (n, n100) {
+            (0, _) => "zero",
+            (1, _) => "one",
+            (2, _) => "two",
+            (_, 3..=10) => "few",
+            (_, 11..=99) => "many",
+            _ => "other",
+        },
+        _ => {
+            if n == 1 {
+                "one"
+            } else {
+                "other"
+            }
+        }
+    }
+}
+
+/// Localized text an element shows, resolved every time it is drawn.
+#[derive(Clone, Debug, Default)]
+pub struct LocalizedText {
+    pub id: String,
+    pub args: Vec<(String, String)>,
+    /// selects the plural form and replaces `{count}`
+    pub count: Option<i64>,
+}
+
+impl LocalizedText {
+    pub fn new(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn arg(mut self, name: &str, value: &str) -> Self {
+        self.set_arg(name, value);
+        self
+    }
+
+    pub fn count(mut self, count: i64) -> Self {
+        self.count = Some(count);
+        self
+    }
+
+    /// Add or replace the argument *name*.
+    pub fn set_arg(&mut self, name: &str, value: &str) {
+        match self.args.iter_mut().find(|(arg, _)| arg == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.args.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn resolve(&self, localization: &Localization) -> String {
+        let args: Vec<(&str, &str)> = self
+            .args
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        localization.resolve(&self.id, self.count, &args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substitute_arguments() {
+        let args = [("name", "world"), ("count", "3")];
+        assert_eq!(substitute("Hello {name}!", &args), "Hello world!");
+        assert_eq!(substitute("{count} of {count}", &args), "3 of 3");
+        assert_eq!(substitute("{unknown} stays", &args), "{unknown} stays");
+        assert_eq!(substitute("{{name}} and }}", &args), "{name} and }");
+        assert_eq!(substitute("open { brace", &args), "open { brace");
+        assert_eq!(substitute("", &args), "");
+    }
+
+    #[test]
+    fn plural_categories() {
+        for (count, category) in [(0, "other"), (1, "one"), (2, "other"), (-1, "one")] {
+            assert_eq!(plural_category("en", count), category);
+            assert_eq!(plural_category("en-US", count), category);
+        }
+        assert_eq!(plural_category("fr", 0), "one");
+        assert_eq!(plural_category("fr", 2), "other");
+        assert_eq!(plural_category("ja", 1), "other");
+
+        let russian = [
+            (1, "one"),
+            (2, "few"),
+            (5, "many"),
+            (11, "many"),
+            (12, "many"),
+            (21, "one"),
+            (22, "few"),
+            (111, "many"),
+        ];
+        for (count, category) in russian {
+            assert_eq!(plural_category("ru", count), category, "{count}");
+        }
+        assert_eq!(plural_category("pl", 1), "one");
+        assert_eq!(plural_category("pl", 21), "many");
+        assert_eq!(plural_category("pl", 24), "few");
+        assert_eq!(plural_category("cs", 3), "few");
+        assert_eq!(plural_category("cs", 5), "other");
+
+        let arabic = [
+            (0, "zero"),
+            (1, "one"),
+            (2, "two"),
+            (3, "few"),
+            (102, "other"),
+            (111, "many"),
+            (100, "other"),
+        ];
+        for (count, category) in arabic {
+            assert_eq!(plural_category("ar", count), category, "{count}");
+        }
+    }
+}
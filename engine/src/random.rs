@@ -0,0 +1,167 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::*;
+
+/// Stream names used by the engine. Games can add their own.
+pub mod streams {
+    pub const GAMEPLAY: &str = "gameplay";
+    pub const PARTICLES: &str = "particles";
+    pub const AI: &str = "ai";
+}
+
+/// Deterministic random number generator (SplitMix64).
+/// The same seed gives the same sequence on every platform.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// uniform in `[0, 1)`
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// uniform in `[min, max)`
+    pub fn range_f32(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    /// uniform in `[min, max)`. Returns *min* if the range is empty.
+    pub fn range_i32(&mut self, min: i32, max: i32) -> i32 {
+        if max <= min {
+            return min;
+        }
+        let span = (max as i64 - min as i64) as u64;
+        (min as i64 + (self.next_u64() % span) as i64) as i32
+    }
+
+    /// `true` with *probability* (0.0 to 1.0)
+    pub fn chance(&mut self, probability: f32) -> bool {
+        self.next_f32() < probability
+    }
+
+    /// Uniformly chosen element. `None` if *items* is empty.
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            return None;
+        }
+        items.get(self.range_i32(0, items.len() as i32) as usize)
+    }
+
+    /// Index chosen with probability proportional to its weight.
+    /// Negative weights count as zero. `None` if every weight is zero.
+    pub fn weighted_index(&mut self, weights: &[f32]) -> Option<usize> {
+        let total: f32 = weights.iter().map(|weight| weight.max(0.0)).sum();
+        if total <= 0.0 {
+            return None;
+        }
+        let mut pick = self.range_f32(0.0, total);
+        for (idx, weight) in weights.iter().enumerate() {
+            let weight = weight.max(0.0);
+            if pick < weight {
+                return Some(idx);
+            }
+            pick -= weight;
+        }
+        // rounding error at the end of the range
+        weights.iter().rposition(|weight| *weight > 0.0)
+    }
+
+    /// Element chosen with probability proportional to *weight*.
+    pub fn choose_weighted<'a, T, F>(&mut self, items: &'a [T], weight: F) -> Option<&'a T>
+    where
+        F: Fn(&T) -> f32,
+    {
+        let weights: Vec<f32> = items.iter().map(weight).collect();
+        self.weighted_index(&weights).map(|idx| &items[idx])
+    }
+
+    /// Fisher-Yates shuffle
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for idx in (1..items.len()).rev() {
+            let other = self.range_i32(0, idx as i32 + 1) as usize;
+            items.swap(idx, other);
+        }
+    }
+
+    /// Uniform point inside *rect*.
+    pub fn point_in_rect(&mut self, rect: Rect) -> Vec2 {
+        Vec2 {
+            x: self.range_i32(rect.x, rect.x + rect.w.max(1)),
+            y: self.range_i32(rect.y, rect.y + rect.h.max(1)),
+        }
+    }
+
+    /// Independent generator seeded from this one.
+    pub fn fork(&mut self) -> Rng {
+        Rng::new(self.next_u64())
+    }
+}
+
+/// Named random streams derived from one seed.
+///
+/// Each stream is seeded from the seed and its name, so drawing from one stream
+/// never changes the numbers of another. Particles can't desync gameplay, for example.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct RandomService {
+    seed: u64,
+    streams: BTreeMap<String, Rng>,
+}
+
+impl RandomService {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            streams: BTreeMap::new(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Restart every stream from *seed*.
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.streams.clear();
+    }
+
+    /// Stream *name*, created on first use.
+    pub fn stream(&mut self, name: &str) -> &mut Rng {
+        let seed = self.seed;
+        self.streams
+            .entry(name.to_string())
+            .or_insert_with(|| Rng::new(seed ^ hash_name(name)))
+    }
+
+    /// The `gameplay` stream
+    pub fn gameplay(&mut self) -> &mut Rng {
+        self.stream(streams::GAMEPLAY)
+    }
+}
+
+/// FNV-1a hash of the stream name
+fn hash_name(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
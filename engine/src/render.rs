@@ -1,18 +1,46 @@
+use std::sync::Arc;
+
+use crate::config::{EngineConfig, WindowMode};
+use crate::debug::DebugOverlay;
+use crate::error::Error;
+use crate::render::texture::Texture;
 use crate::types::*;
+use crate::vfs::Vfs;
+use batch::SpriteBatch;
 use font::Font;
 use manager::TextureManager;
+use post::PostEffect;
+use sdl2::image::LoadSurface;
+use target::RenderTarget;
 
+pub mod batch;
 pub mod font;
+mod loader;
 pub mod manager;
+pub mod post;
+pub mod primitive;
+pub mod rich_text;
+pub mod target;
 pub mod texture;
 
+/// color to clear the screen with
+const CLEAR_COLOR: Color = Color::rgb(200, 150, 250);
+
 /// Type alias for sdl2 canvas
 pub type Canvas<'a> = &'a mut sdl2::render::Canvas<sdl2::video::Window>;
 
 /// Information for every render process
 pub struct RenderInfo {
+    /// size of the screen in pixels.
+    /// it is the virtual resolution while rendering a scene in virtual resolution mode.
     pub screen_size: Vec2,
     pub frame: usize,
+    /// number of batched draw calls in the current frame
+    pub draw_calls: usize,
+    /// number of sprites drawn in the current frame
+    pub sprites: usize,
+    /// number of entities culled in the current frame
+    pub culled: usize,
 }
 
 pub struct Renderer {
@@ -22,50 +50,88 @@ pub struct Renderer {
     pub render_info: RenderInfo,
 
     pub fonts: Vec<Font>,
+
+    /// post-processing passes applied to the final frame, in order.
+    pub post_effects: Vec<PostEffect>,
+
+    batch: SpriteBatch,
+    post_texture: Option<sdl2::render::Texture>,
+    /// fixed size target the scene is rendered into, when virtual resolution is set.
+    virtual_target: Option<RenderTarget>,
 }
 
 impl Renderer {
-    pub(crate) fn new(video_subsystem: &sdl2::VideoSubsystem) -> Self {
-        let window = video_subsystem
-            .window("example title", 800, 600)
+    pub(crate) fn new(
+        video_subsystem: &sdl2::VideoSubsystem,
+        config: &EngineConfig,
+        vfs: Arc<Vfs>,
+    ) -> Self {
+        let mut window_builder = video_subsystem.window(
+            config.title.as_str(),
+            config.size.x as u32,
+            config.size.y as u32,
+        );
+        window_builder
             .position_centered()
             .allow_highdpi()
-            .resizable()
-            .build()
-            .unwrap();
+            .resizable();
+        match config.window_mode {
+            WindowMode::Windowed => (),
+            WindowMode::Fullscreen => {
+                window_builder.fullscreen();
+            }
+            WindowMode::Borderless => {
+                window_builder.fullscreen_desktop();
+            }
+        }
+        let mut window = window_builder.build().unwrap();
+
+        if let Some(min_size) = config.min_size {
+            window
+                .set_minimum_size(min_size.x as u32, min_size.y as u32)
+                .unwrap();
+        }
+        if let Some(icon_path) = &config.icon {
+            match sdl2::surface::Surface::from_file(icon_path) {
+                Ok(icon) => window.set_icon(icon),
+                Err(err) => println!("failed to load window icon {icon_path:?} : {err}"),
+            }
+        }
 
-        let mut canvas = window
-            .into_canvas()
-            .target_texture()
-            .present_vsync()
-            .build()
-            .unwrap();
+        let mut canvas_builder = window.into_canvas().target_texture();
+        if config.vsync {
+            canvas_builder = canvas_builder.present_vsync();
+        }
+        let mut canvas = canvas_builder.build().unwrap();
 
         // init canvas
         canvas.set_draw_color(sdl2::pixels::Color::RGB(0, 0, 0));
+        canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
         canvas.clear();
 
         canvas.present();
 
         // render info
-        let (window_width, window_height) = canvas.window().size();
+        // use drawable size, which differs from the window size on high-DPI displays.
+        let (output_width, output_height) = canvas.output_size().unwrap();
         let render_info = RenderInfo {
             screen_size: Vec2 {
-                x: window_width as i32,
-                y: window_height as i32,
+                x: output_width as i32,
+                y: output_height as i32,
             },
             frame: 0,
+            draw_calls: 0,
+            sprites: 0,
+            culled: 0,
         };
 
         let texture_creator = canvas.texture_creator();
-        let mut texture_manager = TextureManager::new(texture_creator);
+        let mut texture_manager = TextureManager::new(texture_creator, vfs);
 
         // load font now
         // TODO: move to API
 
-        texture_manager
-            .load("font", std::path::Path::new("assets/font.json"))
-            .unwrap();
+        texture_manager.load("font", "assets/font.json").unwrap();
         let font0 = crate::render::font::load_font(
             texture_manager.get("font"),
             "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789 .,!?",
@@ -76,23 +142,166 @@ impl Renderer {
             texture_manager,
             render_info,
             fonts: vec![font0],
+            post_effects: Vec::new(),
+            batch: SpriteBatch::default(),
+            post_texture: None,
+            virtual_target: None,
         }
     }
 
-    pub(crate) fn render(&mut self, scene: &crate::game::scene::Scene) {
-        // clear canvas
-        self.canvas
-            .set_draw_color(sdl2::pixels::Color::RGB(200, 150, 250));
-        self.canvas.clear();
+    pub(crate) fn render(&mut self, scene: &crate::game::scene::Scene, debug: &DebugOverlay) {
+        self.render_info.draw_calls = 0;
+        self.render_info.sprites = 0;
+        self.render_info.culled = 0;
+
+        match self.virtual_target.clone() {
+            Some(target) => {
+                self.render_to_target(&target, CLEAR_COLOR, |renderer| {
+                    scene.render(renderer);
+                    renderer.flush();
+                    renderer.apply_post_effects();
+                });
+
+                // upscale with letterbox
+                self.canvas.set_draw_color(Color::BLACK);
+                self.canvas.clear();
+                if let Some(viewport) = self.virtual_viewport() {
+                    target.texture().draw(&mut self.canvas, viewport);
+                }
+            }
+            None => {
+                self.canvas.set_draw_color(CLEAR_COLOR);
+                self.canvas.clear();
+
+                scene.render(self);
+                self.flush();
+                self.apply_post_effects();
+            }
+        }
 
-        scene.render(self);
+        debug.draw(self, scene);
 
         self.canvas.present();
         self.render_info.frame += 1;
     }
 
-    pub(crate) fn set_screen_size(&mut self, size: Vec2) {
-        self.render_info.screen_size = size;
+    /// Draw the frame *idx* of *texture* into *screen_rect* through the sprite batch.
+    /// Call `flush` before drawing anything else without the batch.
+    pub fn draw_sprite(&mut self, texture: &Texture, screen_rect: Rect, idx: usize) {
+        self.draw_sprite_tinted(texture, screen_rect, idx, Color::WHITE);
+    }
+
+    /// Same as `draw_sprite`, but the texture color is multiplied by *color*.
+    pub fn draw_sprite_tinted(
+        &mut self,
+        texture: &Texture,
+        screen_rect: Rect,
+        idx: usize,
+        color: Color,
+    ) {
+        let (from_rect, to_rect) = texture.frame_rects(screen_rect, idx);
+        self.render_info.draw_calls += self.batch.push(
+            &mut self.canvas,
+            texture.sdl_texture(),
+            from_rect,
+            to_rect,
+            color,
+        );
+        self.render_info.sprites += 1;
+    }
+
+    /// Draw every batched sprite.
+    pub fn flush(&mut self) {
+        self.render_info.draw_calls += self.batch.flush(&mut self.canvas);
+    }
+
+    /// Update the screen size from the drawable size of the window.
+    pub(crate) fn update_screen_size(&mut self) {
+        let (width, height) = self.canvas.output_size().unwrap();
+        self.render_info.screen_size = Vec2 {
+            x: width as i32,
+            y: height as i32,
+        };
+    }
+
+    pub(crate) fn set_window_mode(&mut self, mode: WindowMode) -> Result<(), Error> {
+        let fullscreen_type = match mode {
+            WindowMode::Windowed => sdl2::video::FullscreenType::Off,
+            WindowMode::Fullscreen => sdl2::video::FullscreenType::True,
+            WindowMode::Borderless => sdl2::video::FullscreenType::Desktop,
+        };
+        self.canvas
+            .window_mut()
+            .set_fullscreen(fullscreen_type)
+            .map_err(|err| Error::InitFailure(format!("changing window mode failed : {err}")))?;
+        self.update_screen_size();
+        Ok(())
+    }
+
+    /// Resize the window to *size* in window coordinates.
+    pub(crate) fn set_window_size(&mut self, size: Vec2) -> Result<(), Error> {
+        self.canvas
+            .window_mut()
+            .set_size(size.x as u32, size.y as u32)
+            .map_err(|err| Error::InitFailure(format!("changing window size failed : {err}")))?;
+        self.update_screen_size();
+        Ok(())
+    }
+
+    /// Render the scene at fixed *size* and upscale it to the window by integer scale.
+    /// The remaining area is letterboxed. `None` renders at the window size.
+    pub fn set_virtual_resolution(&mut self, size: Option<Vec2>) -> Result<(), Error> {
+        self.virtual_target = match size {
+            Some(size) => {
+                let target = self.create_render_target(size)?;
+                // SAFETY: the texture is alive as long as the target.
+                unsafe {
+                    sdl2::sys::SDL_SetTextureScaleMode(
+                        target.texture().sdl_texture().raw(),
+                        sdl2::sys::SDL_ScaleMode::SDL_ScaleModeNearest,
+                    );
+                }
+                Some(target)
+            }
+            None => None,
+        };
+        Ok(())
+    }
+
+    /// Region of the window the virtual screen is drawn to, in drawable pixels.
+    /// `None` if virtual resolution is not set.
+    pub fn virtual_viewport(&self) -> Option<Rect> {
+        let virtual_size = self.virtual_target.as_ref()?.size();
+        Some(letterbox_viewport(
+            self.render_info.screen_size,
+            virtual_size,
+        ))
+    }
+
+    /// transforms *point* in window coordinates, like mouse position, to screen space.
+    /// It handles both high-DPI scaling and virtual resolution.
+    /// Call it with the positions of mouse events before using them in the scene.
+    pub fn window_to_screen(&self, point: Vec2) -> Vec2 {
+        let (window_width, window_height) = self.canvas.window().size();
+        window_to_screen(
+            point,
+            Vec2 {
+                x: window_width as i32,
+                y: window_height as i32,
+            },
+            self.render_info.screen_size,
+            self.virtual_target.as_ref().map(RenderTarget::size),
+        )
+    }
+
+    /// transforms point from view space to screen space.
+    pub fn view_to_screen(&self, view_point: Vec2) -> Vec2 {
+        let screen_size = self.render_info.screen_size;
+        Vec2 {
+            x: view_point.x + screen_size.x / 2,
+            // flip vertically to change y-axis direction
+            y: screen_size.y - (view_point.y + screen_size.y / 2),
+        }
     }
 
     /// transforms rect from view space to screen space.
@@ -115,3 +324,84 @@ impl Renderer {
         }
     }
 }
+
+/// Region of *output_size* the virtual screen is drawn to,
+/// scaled by a whole number and centered with black bars.
+fn letterbox_viewport(output_size: Vec2, virtual_size: Vec2) -> Rect {
+    let scale = (output_size.x / virtual_size.x)
+        .min(output_size.y / virtual_size.y)
+        .max(1);
+    let size = virtual_size * scale;
+    Rect::from_start_size((output_size - size) / 2, size)
+}
+
+/// *point* in a window of *window_size* to screen space.
+/// *output_size* is the drawable size in pixels, larger than the window on high-DPI displays.
+fn window_to_screen(
+    point: Vec2,
+    window_size: Vec2,
+    output_size: Vec2,
+    virtual_size: Option<Vec2>,
+) -> Vec2 {
+    let pixel = Vec2 {
+        x: point.x * output_size.x / window_size.x.max(1),
+        y: point.y * output_size.y / window_size.y.max(1),
+    };
+
+    match virtual_size {
+        Some(virtual_size) => {
+            let viewport = letterbox_viewport(output_size, virtual_size);
+            let scale = viewport.w / virtual_size.x;
+            (pixel - viewport.point_left_bottom()) / scale
+        }
+        None => pixel,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn high_dpi_scales_to_drawable_pixels() {
+        let screen = window_to_screen(
+            Vec2 { x: 100, y: 50 },
+            Vec2 { x: 800, y: 600 },
+            Vec2 { x: 1600, y: 1200 },
+            None,
+        );
+        assert_eq!(screen, Vec2 { x: 200, y: 100 });
+    }
+
+    #[test]
+    fn letterbox_centers_whole_scale() {
+        // 320x180 fits 3 times into 1024x768 with bars on every side
+        let viewport = letterbox_viewport(Vec2 { x: 1024, y: 768 }, Vec2 { x: 320, y: 180 });
+        assert_eq!(viewport.point_left_bottom(), Vec2 { x: 32, y: 114 });
+        assert_eq!((viewport.w, viewport.h), (960, 540));
+    }
+
+    #[test]
+    fn virtual_resolution_removes_offset_and_scale() {
+        let window = Vec2 { x: 1024, y: 768 };
+        let virtual_size = Some(Vec2 { x: 320, y: 180 });
+
+        let corner = window_to_screen(Vec2 { x: 32, y: 114 }, window, window, virtual_size);
+        assert_eq!(corner, Vec2 { x: 0, y: 0 });
+
+        let center = window_to_screen(Vec2 { x: 512, y: 384 }, window, window, virtual_size);
+        assert_eq!(center, Vec2 { x: 160, y: 90 });
+    }
+
+    #[test]
+    fn high_dpi_with_virtual_resolution() {
+        // window of 640x360 points drawn at 1280x720 pixels, virtual screen 320x180 at scale 4
+        let screen = window_to_screen(
+            Vec2 { x: 320, y: 180 },
+            Vec2 { x: 640, y: 360 },
+            Vec2 { x: 1280, y: 720 },
+            Some(Vec2 { x: 320, y: 180 }),
+        );
+        assert_eq!(screen, Vec2 { x: 160, y: 90 });
+    }
+}
@@ -0,0 +1,107 @@
+use std::rc::Rc;
+
+use sdl2::sys::{SDL_Color, SDL_FPoint, SDL_Vertex};
+
+use crate::render::Canvas;
+use crate::types::*;
+
+/// Collects sprites sharing the same texture and draws them with a single
+/// `SDL_RenderGeometry` call.
+/// Draw order is kept: the batch is flushed whenever the texture changes.
+#[derive(Default)]
+pub struct SpriteBatch {
+    texture: Option<Rc<sdl2::render::Texture>>,
+    vertices: Vec<SDL_Vertex>,
+    indices: Vec<i32>,
+}
+
+impl SpriteBatch {
+    /// Add a quad copying *from_rect* of *texture* into *to_rect*, multiplied by *color*.
+    /// Returns the number of draw calls issued, which is 1 if the previous batch was flushed.
+    pub fn push(
+        &mut self,
+        canvas: Canvas,
+        texture: &Rc<sdl2::render::Texture>,
+        from_rect: Rect,
+        to_rect: Rect,
+        color: Color,
+    ) -> usize {
+        let mut draw_calls = 0;
+        if !self
+            .texture
+            .as_ref()
+            .is_some_and(|current| Rc::ptr_eq(current, texture))
+        {
+            draw_calls = self.flush(canvas);
+            self.texture = Some(texture.clone());
+        }
+
+        let sdl2::render::TextureQuery { width, height, .. } = texture.query();
+        let (width, height) = (width as f32, height as f32);
+
+        let u0 = from_rect.x as f32 / width;
+        let v0 = from_rect.y as f32 / height;
+        let u1 = (from_rect.x + from_rect.w) as f32 / width;
+        let v1 = (from_rect.y + from_rect.h) as f32 / height;
+
+        let x0 = to_rect.x as f32;
+        let y0 = to_rect.y as f32;
+        let x1 = (to_rect.x + to_rect.w) as f32;
+        let y1 = (to_rect.y + to_rect.h) as f32;
+
+        let base = self.vertices.len() as i32;
+        for (x, y, u, v) in [
+            (x0, y0, u0, v0),
+            (x1, y0, u1, v0),
+            (x0, y1, u0, v1),
+            (x1, y1, u1, v1),
+        ] {
+            self.vertices.push(SDL_Vertex {
+                position: SDL_FPoint { x, y },
+                color: SDL_Color {
+                    r: color.r,
+                    g: color.g,
+                    b: color.b,
+                    a: color.a,
+                },
+                tex_coord: SDL_FPoint { x: u, y: v },
+            });
+        }
+        self.indices
+            .extend([0, 1, 2, 1, 3, 2].map(|offset| base + offset));
+
+        draw_calls
+    }
+
+    /// Draw every collected quad.
+    /// Returns the number of draw calls issued.
+    /// The texture is released, so a dropped render target can free it.
+    pub fn flush(&mut self, canvas: Canvas) -> usize {
+        let Some(texture) = self.texture.take() else {
+            return 0;
+        };
+        if self.indices.is_empty() {
+            return 0;
+        }
+
+        // SAFETY: the renderer and texture are alive while borrowed,
+        // and every index points into `vertices`.
+        let result = unsafe {
+            sdl2::sys::SDL_RenderGeometry(
+                canvas.raw(),
+                texture.raw(),
+                self.vertices.as_ptr(),
+                self.vertices.len() as i32,
+                self.indices.as_ptr(),
+                self.indices.len() as i32,
+            )
+        };
+        if result != 0 {
+            println!("SDL_RenderGeometry failed: {}", sdl2::get_error());
+        }
+
+        self.vertices.clear();
+        self.indices.clear();
+        1
+    }
+}
@@ -8,9 +8,13 @@ use crate::types::*;
 pub type Font = Rc<FontInner>;
 
 /// Single-textured font.
+/// Characters missing from the map are drawn from the fallback fonts, in order,
+/// or as the frame after the last glyph when no font has them.
 pub struct FontInner {
     texture: Texture,
-    map: &'static str,
+    /// characters in the order of the texture frames
+    map: Vec<char>,
+    fallback: Option<Font>,
 }
 
 impl FontInner {
@@ -22,8 +26,8 @@ impl FontInner {
                 x = position.x;
                 y += scale.y as i32;
             } else {
-                let idx = self.map.find(char).unwrap_or(self.map.len());
-                self.texture.draw_idx(
+                let (texture, idx) = self.glyph(char).unwrap_or((&self.texture, self.map.len()));
+                texture.draw_idx(
                     canvas,
                     Rect {
                         x,
@@ -37,8 +41,42 @@ impl FontInner {
             }
         })
     }
+
+    /// Texture and frame of *char* in this font or its fallbacks.
+    fn glyph(&self, char: char) -> Option<(&Texture, usize)> {
+        match self.map.iter().position(|c| *c == char) {
+            Some(idx) => Some((&self.texture, idx)),
+            None => self.fallback.as_ref()?.glyph(char),
+        }
+    }
+
+    /// Texture and frame of *char*, the frame after the last glyph if no font has it.
+    pub(crate) fn glyph_or_missing(&self, char: char) -> (&Texture, usize) {
+        self.glyph(char).unwrap_or((&self.texture, self.map.len()))
+    }
+
+    /// Whether this font or one of its fallbacks has *char*.
+    pub fn has_glyph(&self, char: char) -> bool {
+        self.glyph(char).is_some()
+    }
+
+    /// Copy of this font that falls back to *fallback* after its current fallbacks.
+    pub fn with_fallback(&self, fallback: Font) -> Font {
+        Rc::new(FontInner {
+            texture: self.texture.clone(),
+            map: self.map.clone(),
+            fallback: Some(match &self.fallback {
+                Some(current) => current.with_fallback(fallback),
+                None => fallback,
+            }),
+        })
+    }
 }
 
-pub fn load_font(texture: Texture, map: &'static str) -> Result<Font, Error> {
-    Ok(Rc::new(FontInner { texture, map }))
+pub fn load_font(texture: Texture, map: &str) -> Result<Font, Error> {
+    Ok(Rc::new(FontInner {
+        texture,
+        map: map.chars().collect(),
+        fallback: None,
+    }))
 }
@@ -0,0 +1,118 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::error::Error;
+use crate::render::texture::{self, DecodedTexture};
+use crate::vfs::Vfs;
+
+/// upper limit of worker threads
+const MAX_WORKERS: usize = 4;
+
+struct Job {
+    id: u64,
+    path: String,
+}
+
+/// Texture decoded by a worker, waiting to be uploaded.
+pub(crate) struct Decoded {
+    pub id: u64,
+    pub result: Result<DecodedTexture, Error>,
+}
+
+/// Worker threads that read and decode textures in the background.
+/// Requests are started in the order they were made, but several workers finish them
+/// in any order, so results are matched by id.
+/// Dropping it skips the queued jobs and waits for the workers to finish their current one.
+pub(crate) struct Loader {
+    vfs: Arc<Vfs>,
+    /// `None` once dropping, which closes the queue
+    jobs: Option<Sender<Job>>,
+    decoded_sender: Sender<Decoded>,
+    decoded: Receiver<Decoded>,
+    workers: Vec<JoinHandle<()>>,
+    stopping: Arc<AtomicBool>,
+}
+
+impl Loader {
+    pub fn new(vfs: Arc<Vfs>) -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<Job>();
+        let (decoded_sender, decoded) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let stopping = Arc::new(AtomicBool::new(false));
+
+        let count = thread::available_parallelism()
+            .map_or(1, |count| count.get().saturating_sub(1))
+            .clamp(1, MAX_WORKERS);
+        let mut workers = Vec::with_capacity(count);
+        for idx in 0..count {
+            let vfs = vfs.clone();
+            let job_receiver = job_receiver.clone();
+            let decoded_sender = decoded_sender.clone();
+            let stopping = stopping.clone();
+            let spawned = thread::Builder::new()
+                .name(format!("asset loader {idx}"))
+                .spawn(move || loop {
+                    // the lock is released before decoding, so workers decode in parallel
+                    let job = job_receiver.lock().unwrap().recv();
+                    let Ok(Job { id, path }) = job else {
+                        break;
+                    };
+                    if stopping.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let result = texture::decode(&vfs, &path);
+                    if decoded_sender.send(Decoded { id, result }).is_err() {
+                        break;
+                    }
+                });
+            match spawned {
+                Ok(handle) => workers.push(handle),
+                Err(err) => println!("failed to spawn asset loader : {err}"),
+            }
+        }
+
+        Self {
+            vfs,
+            jobs: Some(jobs),
+            decoded_sender,
+            decoded,
+            workers,
+            stopping,
+        }
+    }
+
+    /// Queue the texture at the virtual *path* to be decoded as *id*.
+    /// Without workers, it is decoded right away.
+    pub fn request(&self, id: u64, path: &str) {
+        if let Some(jobs) = self.jobs.as_ref().filter(|_| !self.workers.is_empty()) {
+            let job = Job {
+                id,
+                path: path.to_string(),
+            };
+            if jobs.send(job).is_ok() {
+                return;
+            }
+        }
+        let result = texture::decode(&self.vfs, path);
+        let _ = self.decoded_sender.send(Decoded { id, result });
+    }
+
+    /// Next decoded texture, if any is ready.
+    pub fn try_next(&self) -> Option<Decoded> {
+        self.decoded.try_recv().ok()
+    }
+}
+
+impl Drop for Loader {
+    fn drop(&mut self) {
+        self.stopping.store(true, Ordering::Relaxed);
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            if worker.join().is_err() {
+                println!("asset loader panicked");
+            }
+        }
+    }
+}
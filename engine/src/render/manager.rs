@@ -1,44 +1,314 @@
+use sdl2::image::{InitFlag, Sdl2ImageContext};
 use sdl2::{render::TextureCreator, video::WindowContext};
-use std::{collections::HashMap, path::Path};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
 
 use crate::error::Error;
-use crate::render::texture::{self, Texture};
+use crate::render::loader::Loader;
+use crate::render::texture::{self, DecodedTexture, Texture};
+use crate::types::*;
+use crate::vfs::Vfs;
+
+/// time spent uploading decoded textures per frame.
+/// at least one texture is uploaded every frame.
+const UPLOAD_BUDGET: Duration = Duration::from_millis(4);
+
+enum LoadState {
+    Loading,
+    Ready(Texture),
+    Failed,
+}
+
+/// Texture being loaded in the background.
+/// It resolves to the placeholder texture until the texture is ready,
+/// and stays the placeholder if loading fails.
+///
+/// It is also a future, for `Scheduler` tasks:
+/// ```ignore
+/// let texture = handle.clone().await;
+/// ```
+#[derive(Clone)]
+pub struct TextureHandle {
+    state: Rc<RefCell<LoadState>>,
+    placeholder: Texture,
+}
+
+impl TextureHandle {
+    pub fn get(&self) -> Texture {
+        match &*self.state.borrow() {
+            LoadState::Ready(texture) => texture.clone(),
+            LoadState::Loading | LoadState::Failed => self.placeholder.clone(),
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        matches!(*self.state.borrow(), LoadState::Ready(_))
+    }
+
+    pub fn is_failed(&self) -> bool {
+        matches!(*self.state.borrow(), LoadState::Failed)
+    }
+}
+
+impl Future for TextureHandle {
+    type Output = Texture;
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Texture> {
+        match *self.state.borrow() {
+            LoadState::Loading => Poll::Pending,
+            _ => Poll::Ready(self.get()),
+        }
+    }
+}
+
+/// Progress of an asset group, for loading screens.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LoadProgress {
+    pub total: usize,
+    pub loaded: usize,
+    pub failed: usize,
+}
+
+impl LoadProgress {
+    /// Finished assets over all assets, 1.0 for an empty group.
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            (self.loaded + self.failed) as f32 / self.total as f32
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.loaded + self.failed >= self.total
+    }
+}
+
+/// List of assets loaded together as a group.
+/// ```json
+/// { "textures": { "human": "assets/human.json", "tiles": "assets/tiles.png" } }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct AssetManifest {
+    /// texture name and virtual path
+    #[serde(default)]
+    pub textures: BTreeMap<String, String>,
+}
+
+struct PendingTexture {
+    name: String,
+    /// groups waiting for this texture
+    groups: Vec<String>,
+    state: Rc<RefCell<LoadState>>,
+}
 
 /// Texture manager holding sdl2::render::texture_creator
-/// Index texture by name(&'static str).
+/// Index texture by name.
+///
+/// Textures are loaded right away with `load`, or in the background with `load_async`.
+/// Background loads read and decode files on worker threads,
+/// and are uploaded to the GPU on the main thread by `poll`.
 pub struct TextureManager {
     texture_creator: TextureCreator<WindowContext>,
-    textures: HashMap<&'static str, Texture>,
+    vfs: Arc<Vfs>,
+    textures: HashMap<String, Texture>,
+
+    /// dropped before `_image_context`, so the workers are joined before SDL_image quits
+    loader: Loader,
+    next_load_id: u64,
+    pending: HashMap<u64, PendingTexture>,
+    groups: HashMap<String, LoadProgress>,
+    placeholder: Texture,
+    /// initialized up front, so worker threads don't race on the lazy init of SDL_image
+    _image_context: Option<Sdl2ImageContext>,
 }
 
 impl TextureManager {
-    pub fn new(texture_creator: TextureCreator<WindowContext>) -> Self {
+    pub fn new(texture_creator: TextureCreator<WindowContext>, vfs: Arc<Vfs>) -> Self {
+        let placeholder = texture::upload(
+            &texture_creator,
+            DecodedTexture::checkerboard(16, 16, [Color::rgb(255, 0, 255), Color::rgb(0, 0, 0)]),
+        )
+        .expect("failed to create placeholder texture");
+        let image_context = sdl2::image::init(InitFlag::PNG | InitFlag::JPG)
+            .map_err(|err| println!("failed to initialize SDL_image : {err}"))
+            .ok();
         Self {
             texture_creator,
+            loader: Loader::new(vfs.clone()),
+            vfs,
             textures: HashMap::new(),
+            next_load_id: 0,
+            pending: HashMap::new(),
+            groups: HashMap::new(),
+            placeholder,
+            _image_context: image_context,
         }
     }
 
-    pub fn load(&mut self, name: &'static str, path: &Path) -> Result<(), Error> {
-        let new_texture = if path.extension().is_some_and(|ext| ext == "json") {
-            texture::load_from_json(&self.texture_creator, path)?
-        } else {
-            texture::load_from_file(&self.texture_creator, path)?
+    /// Load the texture at the virtual *path* as *name*.
+    pub fn load(&mut self, name: &str, path: &str) -> Result<(), Error> {
+        if self.textures.contains_key(name) || self.is_pending(name) {
+            return Err(Error::AlreadyExists);
         }
-        .clone();
+        let new_texture = if path.ends_with(".json") {
+            texture::load_from_json(&self.texture_creator, &self.vfs, path)?
+        } else {
+            texture::load_from_file(&self.texture_creator, &self.vfs, path)?
+        };
+        self.textures.insert(name.to_string(), new_texture);
+        println!("loaded texture {name:}");
+        Ok(())
+    }
+
+    /// Load the texture at the virtual *path* as *name* in the background.
+    /// Until it is ready, `get` returns the placeholder texture for *name*.
+    /// Loading a name that is loaded or being loaded returns a handle to it.
+    pub fn load_async(&mut self, name: &str, path: &str) -> TextureHandle {
+        self.queue(name, path, None)
+    }
+
+    /// Load every texture of *assets*, pairs of name and virtual path, as the group *group*.
+    /// Follow it with `progress`.
+    pub fn load_group(&mut self, group: &str, assets: &[(&str, &str)]) -> Vec<TextureHandle> {
+        self.groups.entry(group.to_string()).or_default();
+        assets
+            .iter()
+            .map(|(name, path)| self.queue(name, path, Some(group)))
+            .collect()
+    }
+
+    /// Load the `AssetManifest` at the virtual *path* as the group *group*.
+    pub fn load_manifest(&mut self, group: &str, path: &str) -> Result<(), Error> {
+        let manifest: AssetManifest = serde_json::from_str(&self.vfs.read_to_string(path)?)
+            .map_err(|_| Error::JSONParseFailure)?;
+        let assets: Vec<(&str, &str)> = manifest
+            .textures
+            .iter()
+            .map(|(name, path)| (name.as_str(), path.as_str()))
+            .collect();
+        self.load_group(group, &assets);
+        Ok(())
+    }
+
+    fn queue(&mut self, name: &str, path: &str, group: Option<&str>) -> TextureHandle {
+        let progress = group.map(|group| {
+            let progress = self.groups.entry(group.to_string()).or_default();
+            progress.total += 1;
+            progress
+        });
 
-        if self.textures.contains_key(name) {
-            Err(Error::AlreadyExists)
+        let state = if let Some(texture) = self.textures.get(name) {
+            if let Some(progress) = progress {
+                progress.loaded += 1;
+            }
+            Rc::new(RefCell::new(LoadState::Ready(texture.clone())))
+        } else if let Some(pending) = self.pending.values_mut().find(|p| p.name == name) {
+            pending.groups.extend(group.map(str::to_string));
+            pending.state.clone()
         } else {
-            self.textures.insert(name, new_texture);
-            println!("loaded texture {name:}");
-            Ok(())
+            let state = Rc::new(RefCell::new(LoadState::Loading));
+            let id = self.next_load_id;
+            self.next_load_id += 1;
+            self.pending.insert(
+                id,
+                PendingTexture {
+                    name: name.to_string(),
+                    groups: group.map(str::to_string).into_iter().collect(),
+                    state: state.clone(),
+                },
+            );
+            self.loader.request(id, path);
+            state
+        };
+        TextureHandle {
+            state,
+            placeholder: self.placeholder.clone(),
         }
     }
 
-    pub fn get(&self, name: &'static str) -> Texture {
+    /// Upload textures decoded since the last call. Called once per frame by the engine.
+    pub fn poll(&mut self) {
+        let start = Instant::now();
+        while let Some(decoded) = self.loader.try_next() {
+            let Some(pending) = self.pending.remove(&decoded.id) else {
+                continue;
+            };
+            let result = decoded
+                .result
+                .and_then(|decoded| texture::upload(&self.texture_creator, decoded));
+            let loaded = result.is_ok();
+            match result {
+                Ok(texture) => {
+                    println!("loaded texture {}", pending.name);
+                    *pending.state.borrow_mut() = LoadState::Ready(texture.clone());
+                    self.textures.insert(pending.name, texture);
+                }
+                Err(err) => {
+                    println!("failed to load texture {} : {err:?}", pending.name);
+                    *pending.state.borrow_mut() = LoadState::Failed;
+                }
+            }
+            for group in &pending.groups {
+                if let Some(progress) = self.groups.get_mut(group) {
+                    if loaded {
+                        progress.loaded += 1;
+                    } else {
+                        progress.failed += 1;
+                    }
+                }
+            }
+
+            if start.elapsed() >= UPLOAD_BUDGET {
+                break;
+            }
+        }
+    }
+
+    /// Progress of the group *group*. An unknown group is empty.
+    pub fn progress(&self, group: &str) -> LoadProgress {
+        self.groups.get(group).copied().unwrap_or_default()
+    }
+
+    /// Number of textures still being loaded in the background.
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    fn is_pending(&self, name: &str) -> bool {
+        self.pending.values().any(|pending| pending.name == name)
+    }
+
+    /// Texture shown while a texture is loading.
+    pub fn placeholder(&self) -> Texture {
+        self.placeholder.clone()
+    }
+
+    /// Estimated memory used by loaded textures, in bytes.
+    /// Every pixel is counted as 4 bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.textures
+            .values()
+            .map(|texture| {
+                let size = texture.size();
+                size.x as usize * size.y as usize * 4
+            })
+            .sum()
+    }
+
+    /// Texture named *name*, the placeholder while it is loading in the background.
+    pub fn get(&self, name: &str) -> Texture {
         match self.textures.get(name) {
             Some(txt) => txt.clone(),
+            None if self.is_pending(name) => self.placeholder.clone(),
             None => self
                 .textures
                 .get("no_texture")
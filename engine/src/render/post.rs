@@ -0,0 +1,220 @@
+use std::rc::Rc;
+
+use crate::render::Renderer;
+use crate::types::*;
+
+/// bits per channel of the color lookup table
+const LUT_BITS: u32 = 5;
+const LUT_SIZE: usize = 1 << LUT_BITS;
+
+/// 3D color lookup table used for color grading.
+/// Each channel is quantized to 5 bits before the lookup.
+pub struct ColorLut {
+    table: Vec<[u8; 3]>,
+}
+
+impl ColorLut {
+    /// Build the table by applying *grade* to every entry.
+    pub fn from_fn<F>(grade: F) -> Self
+    where
+        F: Fn(Color) -> Color,
+    {
+        let mut table = Vec::with_capacity(LUT_SIZE * LUT_SIZE * LUT_SIZE);
+        let level = |idx: usize| (idx * 255 / (LUT_SIZE - 1)) as u8;
+        for r in 0..LUT_SIZE {
+            for g in 0..LUT_SIZE {
+                for b in 0..LUT_SIZE {
+                    let color = grade(Color::rgb(level(r), level(g), level(b)));
+                    table.push([color.r, color.g, color.b]);
+                }
+            }
+        }
+        Self { table }
+    }
+
+    /// Map every color to the nearest color in *palette*.
+    pub fn from_palette(palette: &[Color]) -> Self {
+        Self::from_fn(|color| {
+            palette
+                .iter()
+                .copied()
+                .min_by_key(|candidate| {
+                    let dr = color.r as i32 - candidate.r as i32;
+                    let dg = color.g as i32 - candidate.g as i32;
+                    let db = color.b as i32 - candidate.b as i32;
+                    dr * dr + dg * dg + db * db
+                })
+                .unwrap_or(color)
+        })
+    }
+
+    pub fn lookup(&self, r: u8, g: u8, b: u8) -> [u8; 3] {
+        let shift = 8 - LUT_BITS;
+        let idx = ((r as usize >> shift) << (LUT_BITS * 2))
+            | ((g as usize >> shift) << LUT_BITS)
+            | (b as usize >> shift);
+        self.table[idx]
+    }
+}
+
+/// Post-processing pass applied to the final frame.
+/// Passes run on the CPU in the order they are added.
+#[derive(Clone)]
+pub enum PostEffect {
+    ColorGrade(Rc<ColorLut>),
+    /// darken the corners. *strength* is 0.0 to 1.0.
+    Vignette {
+        strength: f32,
+    },
+    /// blend the frame toward *color*. *amount* is 0.0 to 1.0.
+    Fade {
+        color: Color,
+        amount: f32,
+    },
+    /// draw the frame in blocks of *size* pixels.
+    Pixelate {
+        size: i32,
+    },
+    /// darken every other row. *strength* is 0.0 to 1.0.
+    Scanlines {
+        strength: f32,
+    },
+}
+
+impl PostEffect {
+    /// Apply the effect to RGBA *pixels* of *size*.
+    /// Nothing happens when *size* is empty or *pixels* is smaller than *size*.
+    pub fn apply(&self, pixels: &mut [u8], size: Vec2) {
+        if size.x <= 0 || size.y <= 0 {
+            return;
+        }
+        let (w, h) = (size.x as usize, size.y as usize);
+        if pixels.len() < w * h * 4 {
+            return;
+        }
+        match self {
+            PostEffect::ColorGrade(lut) => {
+                for pixel in pixels.chunks_exact_mut(4) {
+                    let [r, g, b] = lut.lookup(pixel[0], pixel[1], pixel[2]);
+                    pixel[0] = r;
+                    pixel[1] = g;
+                    pixel[2] = b;
+                }
+            }
+            PostEffect::Vignette { strength } => {
+                let (cx, cy) = (w as f32 / 2.0, h as f32 / 2.0);
+                for y in 0..h {
+                    let dy = (y as f32 - cy) / cy;
+                    for x in 0..w {
+                        let dx = (x as f32 - cx) / cx;
+                        // squared distance, 1.0 at the corners
+                        let dist = (dx * dx + dy * dy) / 2.0;
+                        let factor = 1.0 - strength * dist;
+                        let pixel = &mut pixels[(y * w + x) * 4..][..3];
+                        pixel
+                            .iter_mut()
+                            .for_each(|c| *c = (*c as f32 * factor) as u8);
+                    }
+                }
+            }
+            PostEffect::Fade { color, amount } => {
+                let target = [color.r, color.g, color.b];
+                for pixel in pixels.chunks_exact_mut(4) {
+                    for (c, t) in pixel.iter_mut().zip(target) {
+                        *c = (*c as f32 + (t as f32 - *c as f32) * amount) as u8;
+                    }
+                }
+            }
+            PostEffect::Pixelate { size } => {
+                let block = (*size).max(1) as usize;
+                for block_y in (0..h).step_by(block) {
+                    for block_x in (0..w).step_by(block) {
+                        // sample the center of the block
+                        let sample_x = (block_x + block / 2).min(w - 1);
+                        let sample_y = (block_y + block / 2).min(h - 1);
+                        let offset = (sample_y * w + sample_x) * 4;
+                        let sample: [u8; 4] = pixels[offset..offset + 4].try_into().unwrap();
+
+                        for y in block_y..(block_y + block).min(h) {
+                            for x in block_x..(block_x + block).min(w) {
+                                pixels[(y * w + x) * 4..][..4].copy_from_slice(&sample);
+                            }
+                        }
+                    }
+                }
+            }
+            PostEffect::Scanlines { strength } => {
+                let factor = 1.0 - strength;
+                for row in pixels.chunks_exact_mut(w * 4).skip(1).step_by(2) {
+                    for pixel in row.chunks_exact_mut(4) {
+                        pixel[..3]
+                            .iter_mut()
+                            .for_each(|c| *c = (*c as f32 * factor) as u8);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Renderer {
+    /// Apply `post_effects` to the current frame.
+    /// It reads the frame back from the GPU, so it is slow for large windows.
+    pub(crate) fn apply_post_effects(&mut self) {
+        if self.post_effects.is_empty() {
+            return;
+        }
+        self.flush();
+
+        // the screen may be a render target, so read it by explicit size
+        let format = sdl2::pixels::PixelFormatEnum::RGBA32;
+        let size = self.render_info.screen_size;
+        // minimized window
+        if size.x <= 0 || size.y <= 0 {
+            return;
+        }
+        let (width, height) = (size.x as u32, size.y as u32);
+        let mut pixels = match self
+            .canvas
+            .read_pixels(sdl2::rect::Rect::new(0, 0, width, height), format)
+        {
+            Ok(pixels) => pixels,
+            Err(err) => {
+                println!("post effects skipped, reading the frame failed : {err}");
+                return;
+            }
+        };
+        for effect in self.post_effects.iter() {
+            effect.apply(&mut pixels, size);
+        }
+
+        // recreate the streaming texture when the output size changed
+        let reusable = self.post_texture.as_ref().is_some_and(|texture| {
+            let query = texture.query();
+            query.width == width && query.height == height
+        });
+        if !reusable {
+            if let Some(old_texture) = self.post_texture.take() {
+                // SAFETY: the old texture is not referenced anywhere else.
+                unsafe { old_texture.destroy() };
+            }
+            match self.canvas.create_texture_streaming(format, width, height) {
+                Ok(texture) => self.post_texture = Some(texture),
+                Err(err) => {
+                    println!("post effects skipped, texture creation failed : {err}");
+                    return;
+                }
+            }
+        }
+        let Some(post_texture) = self.post_texture.as_mut() else {
+            return;
+        };
+        if let Err(err) = post_texture.update(None, &pixels, width as usize * 4) {
+            println!("post effects skipped, texture update failed : {err}");
+            return;
+        }
+        if let Err(err) = self.canvas.copy(post_texture, None, None) {
+            println!("post effects skipped, copy failed : {err}");
+        }
+    }
+}
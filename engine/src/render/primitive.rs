@@ -0,0 +1,282 @@
+use crate::game::scene::Camera;
+use crate::render::Renderer;
+use crate::types::*;
+
+/// Coordinate space of the primitive to draw.
+#[derive(Clone, Copy)]
+pub enum Space<'a> {
+    /// screen pixels. origin is the left top, y-axis goes down.
+    Screen,
+    /// world coordinates, transformed by the camera.
+    World(&'a Camera),
+}
+
+/// Immediate-mode primitive drawing.
+/// Thickness is always in screen pixels, regardless of *space*.
+impl Renderer {
+    pub fn draw_line(&mut self, space: Space, from: Vec2, to: Vec2, color: Color, thickness: i32) {
+        let from = self.point_to_screen(space, from);
+        let to = self.point_to_screen(space, to);
+        self.canvas.set_draw_color(color);
+        self.line_screen(from, to, thickness);
+    }
+
+    /// Draw outline of *rect*. The outline grows inward.
+    pub fn draw_rect(&mut self, space: Space, rect: Rect, color: Color, thickness: i32) {
+        let rect = self.rect_to_screen(space, rect);
+        self.canvas.set_draw_color(color);
+        if thickness <= 1 {
+            self.canvas.draw_rect(rect.into()).unwrap();
+        } else {
+            let t = thickness.min(rect.w / 2).min(rect.h / 2).max(1);
+            let edges: [sdl2::rect::Rect; 4] = [
+                Rect { h: t, ..rect }.into(),
+                Rect {
+                    y: rect.y + rect.h - t,
+                    h: t,
+                    ..rect
+                }
+                .into(),
+                Rect {
+                    y: rect.y + t,
+                    w: t,
+                    h: rect.h - t * 2,
+                    ..rect
+                }
+                .into(),
+                Rect {
+                    x: rect.x + rect.w - t,
+                    y: rect.y + t,
+                    w: t,
+                    h: rect.h - t * 2,
+                }
+                .into(),
+            ];
+            self.canvas.fill_rects(&edges).unwrap();
+        }
+    }
+
+    pub fn fill_rect(&mut self, space: Space, rect: Rect, color: Color) {
+        let rect = self.rect_to_screen(space, rect);
+        self.canvas.set_draw_color(color);
+        self.canvas.fill_rect(Some(rect.into())).unwrap();
+    }
+
+    /// Draw outline of the circle. The outline grows inward.
+    pub fn draw_circle(
+        &mut self,
+        space: Space,
+        center: Vec2,
+        radius: i32,
+        color: Color,
+        thickness: i32,
+    ) {
+        let center = self.point_to_screen(space, center);
+        let radius = self.length_to_screen(space, radius);
+        self.canvas.set_draw_color(color);
+        self.ring_screen(center, radius, (radius - thickness.max(1)).max(0));
+    }
+
+    pub fn fill_circle(&mut self, space: Space, center: Vec2, radius: i32, color: Color) {
+        let center = self.point_to_screen(space, center);
+        let radius = self.length_to_screen(space, radius);
+        self.canvas.set_draw_color(color);
+        self.ring_screen(center, radius, 0);
+    }
+
+    /// Draw outline of the closed polygon.
+    pub fn draw_polygon(&mut self, space: Space, points: &[Vec2], color: Color, thickness: i32) {
+        let points: Vec<Vec2> = points
+            .iter()
+            .map(|point| self.point_to_screen(space, *point))
+            .collect();
+        self.canvas.set_draw_color(color);
+        for (idx, from) in points.iter().enumerate() {
+            let to = points[(idx + 1) % points.len()];
+            self.line_screen(*from, to, thickness);
+        }
+    }
+
+    /// Fill the polygon with even-odd rule.
+    pub fn fill_polygon(&mut self, space: Space, points: &[Vec2], color: Color) {
+        let points: Vec<Vec2> = points
+            .iter()
+            .map(|point| self.point_to_screen(space, *point))
+            .collect();
+        self.canvas.set_draw_color(color);
+        self.polygon_screen(&points);
+    }
+
+    /// Draw line with an arrow head at *to*.
+    pub fn draw_arrow(&mut self, space: Space, from: Vec2, to: Vec2, color: Color, thickness: i32) {
+        let from = self.point_to_screen(space, from);
+        let to = self.point_to_screen(space, to);
+        self.canvas.set_draw_color(color);
+
+        let dx = (to.x - from.x) as f32;
+        let dy = (to.y - from.y) as f32;
+        let len = (dx * dx + dy * dy).sqrt();
+        if len < 1.0 {
+            return;
+        }
+        let (ux, uy) = (dx / len, dy / len);
+        let head_len = ((thickness.max(1) * 4) as f32).max(8.0).min(len);
+        let head_width = head_len * 0.5;
+
+        let base_x = to.x as f32 - ux * head_len;
+        let base_y = to.y as f32 - uy * head_len;
+        let head = [
+            to,
+            Vec2 {
+                x: (base_x - uy * head_width).round() as i32,
+                y: (base_y + ux * head_width).round() as i32,
+            },
+            Vec2 {
+                x: (base_x + uy * head_width).round() as i32,
+                y: (base_y - ux * head_width).round() as i32,
+            },
+        ];
+
+        let base = Vec2 {
+            x: base_x.round() as i32,
+            y: base_y.round() as i32,
+        };
+        self.line_screen(from, base, thickness);
+        self.polygon_screen(&head);
+    }
+
+    /// transforms *point* in *space* to screen space.
+    pub fn point_to_screen(&self, space: Space, point: Vec2) -> Vec2 {
+        match space {
+            Space::Screen => point,
+            Space::World(camera) => self.view_to_screen(camera.transform_point(point)),
+        }
+    }
+
+    /// transforms *rect* in *space* to screen space.
+    pub fn rect_to_screen(&self, space: Space, rect: Rect) -> Rect {
+        match space {
+            Space::Screen => rect,
+            Space::World(camera) => {
+                let view_rect = camera.transform(rect);
+                let left_top = self.view_to_screen(Vec2 {
+                    x: view_rect.x,
+                    y: view_rect.y + view_rect.h,
+                });
+                Rect::from_start_size(left_top, view_rect.size())
+            }
+        }
+    }
+
+    fn length_to_screen(&self, space: Space, length: i32) -> i32 {
+        match space {
+            Space::Screen => length,
+            Space::World(camera) => (length as f32 * camera.zoom()).round() as i32,
+        }
+    }
+
+    /// line in screen space with current draw color
+    fn line_screen(&mut self, from: Vec2, to: Vec2, thickness: i32) {
+        if thickness <= 1 {
+            self.canvas.draw_line(from, to).unwrap();
+            return;
+        }
+
+        let dx = (to.x - from.x) as f32;
+        let dy = (to.y - from.y) as f32;
+        let len = (dx * dx + dy * dy).sqrt();
+        if len < 1.0 {
+            return;
+        }
+        // normal vector with half thickness
+        let nx = -dy / len * thickness as f32 / 2.0;
+        let ny = dx / len * thickness as f32 / 2.0;
+        let offset = |point: Vec2, sign: f32| Vec2 {
+            x: (point.x as f32 + nx * sign).round() as i32,
+            y: (point.y as f32 + ny * sign).round() as i32,
+        };
+        self.polygon_screen(&[
+            offset(from, 1.0),
+            offset(to, 1.0),
+            offset(to, -1.0),
+            offset(from, -1.0),
+        ]);
+    }
+
+    /// filled ring in screen space with current draw color.
+    /// *inner* 0 fills the whole circle.
+    fn ring_screen(&mut self, center: Vec2, outer: i32, inner: i32) {
+        if outer <= 0 {
+            return;
+        }
+        let screen_h = self.render_info.screen_size.y;
+        let mut rects = Vec::new();
+        for dy in -outer..outer {
+            let y = center.y + dy;
+            if y < 0 || y >= screen_h {
+                continue;
+            }
+            // sample at the center of the pixel row
+            let fy = dy as f32 + 0.5;
+            let outer_x = ((outer * outer) as f32 - fy * fy).max(0.0).sqrt().round() as i32;
+            let inner_x = if fy.abs() < inner as f32 {
+                ((inner * inner) as f32 - fy * fy).sqrt().round() as i32
+            } else {
+                0
+            };
+
+            if inner_x == 0 {
+                rects.push(sdl2::rect::Rect::new(
+                    center.x - outer_x,
+                    y,
+                    (outer_x * 2) as u32,
+                    1,
+                ));
+            } else if outer_x > inner_x {
+                let w = (outer_x - inner_x) as u32;
+                rects.push(sdl2::rect::Rect::new(center.x - outer_x, y, w, 1));
+                rects.push(sdl2::rect::Rect::new(center.x + inner_x, y, w, 1));
+            }
+        }
+        self.canvas.fill_rects(&rects).unwrap();
+    }
+
+    /// scanline polygon fill in screen space with current draw color
+    fn polygon_screen(&mut self, points: &[Vec2]) {
+        if points.len() < 3 {
+            return;
+        }
+        let screen_h = self.render_info.screen_size.y;
+        let min_y = points.iter().map(|point| point.y).min().unwrap().max(0);
+        let max_y = points
+            .iter()
+            .map(|point| point.y)
+            .max()
+            .unwrap()
+            .min(screen_h);
+
+        let mut rects = Vec::new();
+        let mut crossings: Vec<f32> = Vec::new();
+        for y in min_y..max_y {
+            let scan_y = y as f32 + 0.5;
+            crossings.clear();
+            for (idx, a) in points.iter().enumerate() {
+                let b = points[(idx + 1) % points.len()];
+                let (ay, by) = (a.y as f32, b.y as f32);
+                if (ay <= scan_y) != (by <= scan_y) {
+                    crossings.push(a.x as f32 + (scan_y - ay) / (by - ay) * (b.x - a.x) as f32);
+                }
+            }
+            crossings.sort_by(f32::total_cmp);
+
+            for span in crossings.chunks_exact(2) {
+                let x0 = span[0].round() as i32;
+                let x1 = span[1].round() as i32;
+                if x1 > x0 {
+                    rects.push(sdl2::rect::Rect::new(x0, y, (x1 - x0) as u32, 1));
+                }
+            }
+        }
+        self.canvas.fill_rects(&rects).unwrap();
+    }
+}
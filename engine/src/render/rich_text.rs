@@ -0,0 +1,494 @@
+use crate::render::font::Font;
+use crate::render::texture::Texture;
+use crate::render::Renderer;
+use crate::types::*;
+
+/// tags that change the style until their closing tag
+const STYLE_TAGS: [&str; 6] = ["color", "size", "b", "i", "wave", "shake"];
+/// default wave height, percent of the glyph height
+const WAVE_AMPLITUDE: i32 = 15;
+/// default shake distance, percent of the glyph size
+const SHAKE_AMPLITUDE: i32 = 8;
+/// size, wave and shake values are clamped to 1 to this percent, so glyph sizes can't overflow
+const MAX_PERCENT: i32 = 1000;
+
+/// Fonts used by rich text.
+/// Missing variants fall back to the closest one: bold italic to bold, then italic, then regular.
+#[derive(Clone)]
+pub struct RichFont {
+    pub regular: Font,
+    pub bold: Option<Font>,
+    pub italic: Option<Font>,
+    pub bold_italic: Option<Font>,
+    /// sprite sheet of the inline icons
+    pub icons: Option<Texture>,
+}
+
+impl RichFont {
+    pub fn new(regular: Font) -> Self {
+        Self {
+            regular,
+            bold: None,
+            italic: None,
+            bold_italic: None,
+            icons: None,
+        }
+    }
+
+    pub fn bold(mut self, font: Font) -> Self {
+        self.bold = Some(font);
+        self
+    }
+
+    pub fn italic(mut self, font: Font) -> Self {
+        self.italic = Some(font);
+        self
+    }
+
+    pub fn bold_italic(mut self, font: Font) -> Self {
+        self.bold_italic = Some(font);
+        self
+    }
+
+    pub fn icons(mut self, texture: Texture) -> Self {
+        self.icons = Some(texture);
+        self
+    }
+
+    fn variant(&self, bold: bool, italic: bool) -> &Font {
+        let candidates = match (bold, italic) {
+            (true, true) => [&self.bold_italic, &self.bold, &self.italic],
+            (true, false) => [&self.bold, &None, &None],
+            (false, true) => [&self.italic, &None, &None],
+            (false, false) => [&None, &None, &None],
+        };
+        candidates
+            .into_iter()
+            .find_map(Option::as_ref)
+            .unwrap_or(&self.regular)
+    }
+}
+
+/// Per-character animation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Effects {
+    /// vertical wave height, percent of the glyph height
+    pub wave: Option<i32>,
+    /// random jitter distance, percent of the glyph size
+    pub shake: Option<i32>,
+}
+
+/// Style of a run of rich text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextStyle {
+    pub color: Color,
+    /// percent of the element scale
+    pub size: i32,
+    pub bold: bool,
+    pub italic: bool,
+    pub effects: Effects,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            size: 100,
+            bold: false,
+            italic: false,
+            effects: Effects::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Icon {
+    /// frame index of the icon sheet
+    Frame(usize),
+    /// first frame of the animation tag of the icon sheet
+    Tag(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GlyphKind {
+    Char(char),
+    Icon(Icon),
+    Newline,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StyledGlyph {
+    pub kind: GlyphKind,
+    pub style: TextStyle,
+}
+
+/// Text parsed from markup, ready to draw.
+///
+/// Tags:
+/// - `[color=#ff8000]`, `[color=#ff800080]` or `[color=red]` ... `[/color]`
+/// - `[size=150]` ... `[/size]` : percent of the element scale, up to 1000
+/// - `[b]` ... `[/b]`, `[i]` ... `[/i]` : bold and italic glyph sheets of the `RichFont`
+/// - `[wave]`, `[wave=30]` ... `[/wave]` : height in percent of the glyph
+/// - `[shake]`, `[shake=10]` ... `[/shake]` : distance in percent of the glyph
+/// - `[icon=3]` or `[icon=coin]` : frame or animation tag of the icon sheet
+///
+/// Tags may overlap, a closing tag undoes what its opening tag changed.
+/// `[[` is a literal `[`, see `escape`. Unknown tags are shown as they are.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RichText {
+    pub glyphs: Vec<StyledGlyph>,
+}
+
+impl RichText {
+    pub fn parse(markup: &str) -> Self {
+        let mut glyphs = Vec::new();
+        let mut style = TextStyle::default();
+        // open tag and the style before it
+        let mut open: Vec<(String, TextStyle)> = Vec::new();
+
+        let mut rest = markup;
+        while let Some(char) = rest.chars().next() {
+            if rest.starts_with("[[") {
+                glyphs.push(StyledGlyph {
+                    kind: GlyphKind::Char('['),
+                    style,
+                });
+                rest = &rest[2..];
+                continue;
+            }
+            if char == '[' {
+                if let Some(end) = rest.find(']') {
+                    if let Some(kind) = apply_tag(&rest[1..end], &mut style, &mut open) {
+                        glyphs.extend(kind.map(|kind| StyledGlyph { kind, style }));
+                        rest = &rest[end + 1..];
+                        continue;
+                    }
+                }
+            }
+            let kind = match char {
+                '\n' => GlyphKind::Newline,
+                char => GlyphKind::Char(char),
+            };
+            glyphs.push(StyledGlyph { kind, style });
+            rest = &rest[char.len_utf8()..];
+        }
+        Self { glyphs }
+    }
+
+    /// Number of glyphs revealed by a typewriter, newlines excluded.
+    pub fn len(&self) -> usize {
+        self.glyphs
+            .iter()
+            .filter(|glyph| glyph.kind != GlyphKind::Newline)
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Text without markup.
+    pub fn plain(&self) -> String {
+        self.glyphs
+            .iter()
+            .filter_map(|glyph| match glyph.kind {
+                GlyphKind::Char(char) => Some(char),
+                GlyphKind::Newline => Some('\n'),
+                GlyphKind::Icon(_) => None,
+            })
+            .collect()
+    }
+
+    /// Draw the first *visible* glyphs at the screen *position*, left top.
+    /// *scale* is the size of a glyph at 100% size.
+    /// Effects are animated by the frame counter of the renderer.
+    pub fn draw(
+        &self,
+        renderer: &mut Renderer,
+        font: &RichFont,
+        position: Vec2,
+        scale: Vec2,
+        visible: usize,
+    ) {
+        let frame = renderer.render_info.frame;
+        let mut shown = 0;
+        let mut y = position.y;
+        for line in self.glyphs.split(|glyph| glyph.kind == GlyphKind::Newline) {
+            // glyphs of a line share the bottom
+            let line_height = line
+                .iter()
+                .map(|glyph| scale.y * glyph.style.size / 100)
+                .max()
+                .unwrap_or(scale.y);
+            let mut x = position.x;
+            for glyph in line {
+                if shown >= visible {
+                    renderer.flush();
+                    return;
+                }
+                let size = Vec2 {
+                    x: scale.x * glyph.style.size / 100,
+                    y: scale.y * glyph.style.size / 100,
+                };
+                let offset = effect_offset(glyph.style.effects, size, frame, shown);
+                let rect = Rect {
+                    x: x + offset.x,
+                    y: y + line_height - size.y + offset.y,
+                    w: size.x,
+                    h: size.y,
+                };
+                match &glyph.kind {
+                    GlyphKind::Char(char) => {
+                        let (texture, idx) = font
+                            .variant(glyph.style.bold, glyph.style.italic)
+                            .glyph_or_missing(*char);
+                        renderer.draw_sprite_tinted(texture, rect, idx, glyph.style.color);
+                    }
+                    GlyphKind::Icon(icon) => {
+                        let idx = font.icons.as_ref().and_then(|icons| match icon {
+                            Icon::Frame(idx) => Some(*idx),
+                            Icon::Tag(tag) => icons.frame_tag(tag).map(|range| *range.start()),
+                        });
+                        if let (Some(icons), Some(idx)) = (&font.icons, idx) {
+                            // icons keep their own colors
+                            let color = Color {
+                                a: glyph.style.color.a,
+                                ..Color::WHITE
+                            };
+                            renderer.draw_sprite_tinted(icons, rect, idx, color);
+                        }
+                    }
+                    GlyphKind::Newline => (),
+                }
+                x += size.x;
+                shown += 1;
+            }
+            y += line_height;
+        }
+        // UI is drawn outside the sprite batch
+        renderer.flush();
+    }
+}
+
+/// *text* with `[` doubled, so `RichText::parse` shows it as it is.
+/// Use it for user input and arguments put into markup.
+pub fn escape(text: &str) -> String {
+    text.replace('[', "[[")
+}
+
+/// Apply the tag *tag*, the text between the brackets.
+/// Returns the glyph the tag inserts, or `None` if it is not a known tag.
+fn apply_tag(
+    tag: &str,
+    style: &mut TextStyle,
+    open: &mut Vec<(String, TextStyle)>,
+) -> Option<Option<GlyphKind>> {
+    if let Some(name) = tag.strip_prefix('/') {
+        if !STYLE_TAGS.contains(&name) {
+            return None;
+        }
+        // restore what the latest matching tag changed, so overlapping tags work too.
+        // a closing tag without an opening tag is ignored.
+        if let Some(idx) = open.iter().rposition(|(open_name, _)| open_name == name) {
+            let (_, before) = open.remove(idx);
+            match name {
+                "color" => style.color = before.color,
+                "size" => style.size = before.size,
+                "b" => style.bold = before.bold,
+                "i" => style.italic = before.italic,
+                "wave" => style.effects.wave = before.effects.wave,
+                _ => style.effects.shake = before.effects.shake,
+            }
+        }
+        return Some(None);
+    }
+
+    let (name, value) = match tag.split_once('=') {
+        Some((name, value)) => (name, Some(value)),
+        None => (tag, None),
+    };
+    let mut next = *style;
+    match (name, value) {
+        ("icon", Some(value)) => {
+            let icon = match value.parse() {
+                Ok(idx) => Icon::Frame(idx),
+                Err(_) => Icon::Tag(value.to_string()),
+            };
+            return Some(Some(GlyphKind::Icon(icon)));
+        }
+        ("color", Some(value)) => next.color = parse_color(value)?,
+        ("size", Some(value)) => {
+            next.size = value
+                .parse::<i32>()
+                .ok()
+                .filter(|size| *size > 0)?
+                .min(MAX_PERCENT)
+        }
+        ("b", None) => next.bold = true,
+        ("i", None) => next.italic = true,
+        ("wave", value) => next.effects.wave = Some(parse_amplitude(value, WAVE_AMPLITUDE)?),
+        ("shake", value) => next.effects.shake = Some(parse_amplitude(value, SHAKE_AMPLITUDE)?),
+        _ => return None,
+    }
+    open.push((name.to_string(), *style));
+    *style = next;
+    Some(None)
+}
+
+/// Percent of an effect tag, *default* without a value.
+fn parse_amplitude(value: Option<&str>, default: i32) -> Option<i32> {
+    match value {
+        Some(value) => Some(value.parse::<i32>().ok()?.clamp(1, MAX_PERCENT)),
+        None => Some(default),
+    }
+}
+
+/// `#rrggbb`, `#rrggbbaa` or a color name.
+fn parse_color(value: &str) -> Option<Color> {
+    let Some(hex) = value.strip_prefix('#') else {
+        return match value {
+            "white" => Some(Color::WHITE),
+            "black" => Some(Color::BLACK),
+            "red" => Some(Color::RED),
+            "green" => Some(Color::GREEN),
+            "blue" => Some(Color::BLUE),
+            "magenta" => Some(Color::MAGENTA),
+            "yellow" => Some(Color::rgb(255, 255, 0)),
+            "cyan" => Some(Color::rgb(0, 255, 255)),
+            "gray" => Some(Color::rgb(128, 128, 128)),
+            _ => None,
+        };
+    };
+    if !hex.is_ascii() || (hex.len() != 6 && hex.len() != 8) {
+        return None;
+    }
+    let channel = |idx: usize| u8::from_str_radix(&hex[idx..idx + 2], 16).ok();
+    Some(Color {
+        r: channel(0)?,
+        g: channel(2)?,
+        b: channel(4)?,
+        a: if hex.len() == 8 { channel(6)? } else { 255 },
+    })
+}
+
+/// Offset of the *idx*-th glyph at *frame*, in screen pixels.
+fn effect_offset(effects: Effects, size: Vec2, frame: usize, idx: usize) -> Vec2 {
+    let mut offset = Vec2 { x: 0, y: 0 };
+    if let Some(amplitude) = effects.wave {
+        let phase = frame as f32 * 0.1 + idx as f32 * 0.5;
+        offset.y += (phase.sin() * (size.y * amplitude) as f32 / 100.0) as i32;
+    }
+    if let Some(amplitude) = effects.shake {
+        // new position every other frame
+        let mut hash = (frame as u64 / 2)
+            .wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            .wrapping_add(idx as u64);
+        hash ^= hash >> 31;
+        hash = hash.wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        hash ^= hash >> 29;
+        let jitter = |bits: u64, extent: i32| {
+            let extent = (extent * amplitude / 100).max(1);
+            (bits % (2 * extent as u64 + 1)) as i32 - extent
+        };
+        offset.x += jitter(hash & 0xffff, size.x);
+        offset.y += jitter(hash >> 32, size.y);
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(text: &RichText) -> Vec<(char, TextStyle)> {
+        text.glyphs
+            .iter()
+            .filter_map(|glyph| match glyph.kind {
+                GlyphKind::Char(char) => Some((char, glyph.style)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn plain_text_and_escapes() {
+        let text = RichText::parse("a [[b] c\nd");
+        assert_eq!(text.plain(), "a [b] c\nd");
+        assert_eq!(text.len(), 8);
+        assert!(text
+            .glyphs
+            .iter()
+            .all(|glyph| glyph.style == TextStyle::default()));
+
+        let markup = format!("Hi {}!", escape("[b]Bob[/b]"));
+        assert_eq!(RichText::parse(&markup).plain(), "Hi [b]Bob[/b]!");
+    }
+
+    #[test]
+    fn styles_and_closing_tags() {
+        let text = RichText::parse("[b]a[color=#ff000080]b[/b]c[/color]d");
+        let glyphs = chars(&text);
+        let red = Color {
+            r: 255,
+            g: 0,
+            b: 0,
+            a: 128,
+        };
+        assert_eq!(text.plain(), "abcd");
+        assert!(glyphs[0].1.bold && glyphs[0].1.color == Color::WHITE);
+        assert!(glyphs[1].1.bold && glyphs[1].1.color == red);
+        // overlapping tags: closing bold keeps the color
+        assert!(!glyphs[2].1.bold && glyphs[2].1.color == red);
+        assert_eq!(glyphs[3].1, TextStyle::default());
+
+        let text = RichText::parse("[i][color=yellow]x[/i]y[/color][/b]z");
+        let glyphs = chars(&text);
+        assert!(glyphs[0].1.italic);
+        assert_eq!(glyphs[1].1.color, Color::rgb(255, 255, 0));
+        assert!(!glyphs[1].1.italic);
+        assert_eq!(glyphs[2].1, TextStyle::default());
+    }
+
+    #[test]
+    fn unknown_and_invalid_tags_are_shown() {
+        for markup in [
+            "[foo]",
+            "[color=nope]",
+            "[size=0]",
+            "[size=x]",
+            "[wave=big]",
+            "[b",
+        ] {
+            assert_eq!(RichText::parse(markup).plain(), markup);
+        }
+    }
+
+    #[test]
+    fn values_are_clamped() {
+        let text = RichText::parse("[size=99999999]a[/size][wave=-5]b[/wave][shake=5000]c");
+        let glyphs = chars(&text);
+        assert_eq!(glyphs[0].1.size, MAX_PERCENT);
+        assert_eq!(glyphs[1].1.effects.wave, Some(1));
+        assert_eq!(glyphs[2].1.effects.shake, Some(MAX_PERCENT));
+
+        let text = RichText::parse("[wave]a[shake=20]b");
+        let glyphs = chars(&text);
+        assert_eq!(glyphs[0].1.effects.wave, Some(WAVE_AMPLITUDE));
+        assert_eq!(glyphs[1].1.effects.shake, Some(20));
+    }
+
+    #[test]
+    fn icons() {
+        let text = RichText::parse("[icon=3][icon=coin]");
+        let kinds: Vec<&GlyphKind> = text.glyphs.iter().map(|glyph| &glyph.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                &GlyphKind::Icon(Icon::Frame(3)),
+                &GlyphKind::Icon(Icon::Tag("coin".to_string()))
+            ]
+        );
+        assert_eq!(text.len(), 2);
+        assert_eq!(text.plain(), "");
+    }
+}
@@ -0,0 +1,101 @@
+use crate::error::Error;
+use crate::game::entity::Entity;
+use crate::game::scene::Scene;
+use crate::render::texture::{self, Texture};
+use crate::render::Renderer;
+use crate::types::*;
+
+/// Offscreen texture to render into.
+/// Use it for minimaps, mirrors or UI previews.
+#[derive(Clone)]
+pub struct RenderTarget {
+    texture: Texture,
+    size: Vec2,
+}
+
+impl RenderTarget {
+    /// The rendered result. Draw it like any other texture.
+    pub fn texture(&self) -> Texture {
+        self.texture.clone()
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.size
+    }
+}
+
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        // the last clone frees the texture, unless `texture()` is still held somewhere
+        // SAFETY: the target and its texture are dropped here and never drawn again.
+        unsafe {
+            texture::destroy_if_unique(&mut self.texture);
+        }
+    }
+}
+
+impl Renderer {
+    /// Create an offscreen render target of *size* pixels.
+    pub fn create_render_target(&mut self, size: Vec2) -> Result<RenderTarget, Error> {
+        let mut sdl_texture = self
+            .canvas
+            .create_texture_target(None, size.x as u32, size.y as u32)
+            .map_err(|_| Error::TextureCreateFailure)?;
+        sdl_texture.set_blend_mode(sdl2::render::BlendMode::Blend);
+
+        Ok(RenderTarget {
+            texture: texture::from_sdl_texture(sdl_texture),
+            size,
+        })
+    }
+
+    /// Run *draw* with *target* as the screen.
+    /// The target is cleared with *clear_color* first.
+    /// While drawing, `render_info.screen_size` is the size of the target.
+    pub fn render_to_target<F>(&mut self, target: &RenderTarget, clear_color: Color, draw: F)
+    where
+        F: FnOnce(&mut Renderer),
+    {
+        self.flush();
+
+        // SAFETY: the renderer and both textures outlive this call,
+        // and the previous target is restored before returning.
+        let previous_target = unsafe {
+            let previous_target = sdl2::sys::SDL_GetRenderTarget(self.canvas.raw());
+            sdl2::sys::SDL_SetRenderTarget(self.canvas.raw(), target.texture.sdl_texture().raw());
+            previous_target
+        };
+        let screen_size = self.render_info.screen_size;
+        self.render_info.screen_size = target.size;
+
+        self.canvas.set_draw_color(clear_color);
+        self.canvas.clear();
+        draw(self);
+        self.flush();
+
+        self.render_info.screen_size = screen_size;
+        unsafe {
+            sdl2::sys::SDL_SetRenderTarget(self.canvas.raw(), previous_target);
+        }
+    }
+
+    /// Render *scene* with its camera into *target*.
+    pub fn render_scene_to_target(&mut self, target: &RenderTarget, scene: &Scene) {
+        self.render_to_target(target, Color::rgba(0, 0, 0, 0), |renderer| {
+            scene.render(renderer)
+        });
+    }
+
+    /// Render *root* and its children with the scene camera into *target*.
+    /// The rest of the scene is left out.
+    pub fn render_entities_to_target(
+        &mut self,
+        target: &RenderTarget,
+        scene: &Scene,
+        root: &Entity,
+    ) {
+        self.render_to_target(target, Color::rgba(0, 0, 0, 0), |renderer| {
+            scene.render_subtree(renderer, root)
+        });
+    }
+}
@@ -1,13 +1,15 @@
-use std::fs;
-use std::path::Path;
+use std::ops::RangeInclusive;
 use std::rc::Rc;
 
 use crate::error::Error;
 use crate::render::Canvas;
 use crate::types::*;
+use crate::vfs::{self, Vfs};
 
-use sdl2::image::LoadTexture;
+use sdl2::image::ImageRWops;
+use sdl2::pixels::PixelFormatEnum;
 use sdl2::render::TextureCreator;
+use sdl2::rwops::RWops;
 use sdl2::video::WindowContext;
 
 use serde::{Deserialize, Serialize};
@@ -29,10 +31,40 @@ struct SubTexturePosition {
     sourceSize: Size,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+struct SliceKey {
+    /// frame index this key starts from
+    frame: usize,
+    /// slice region in the sprite
+    bounds: Rect,
+    /// stretchable center region, relative to *bounds*. only exists for 9-slices.
+    center: Option<Rect>,
+}
+
+/// Slice defined in Aseprite. (`Sprite->Slice`)
+#[derive(Serialize, Deserialize, Debug)]
+struct Slice {
+    name: String,
+    keys: Vec<SliceKey>,
+}
+
+/// Animation tag defined in Aseprite, a range of frames.
+#[derive(Serialize, Deserialize, Debug)]
+struct FrameTag {
+    name: String,
+    from: usize,
+    to: usize,
+}
+
+#[allow(non_snake_case)]
 #[derive(Serialize, Deserialize, Debug)]
 struct FileMetadata {
     image: String,
     size: Size,
+    #[serde(default)]
+    slices: Vec<Slice>,
+    #[serde(default)]
+    frameTags: Vec<FrameTag>,
 }
 
 /// Requirement for the sprite sheet JSON file
@@ -41,6 +73,8 @@ struct FileMetadata {
 ///     each contain the key *frame*, which represents the rectangle.
 ///     - *frame* : composed of *x*, *y*, *w*, *h*
 /// - *meta* : metadata for the target image file
+///     - *slices* (optional) : 9-slice insets, read by `TextureInner::nine_slice`
+///     - *frameTags* (optional) : animation frame ranges, read by `TextureInner::frame_tag`
 /// For more detail, see example at `assets/font.json`.
 #[derive(Serialize, Deserialize, Debug)]
 struct Metadata {
@@ -57,58 +91,203 @@ pub type Texture = Rc<TextureInner>;
 pub struct TextureInner {
     sdl_texture: Rc<sdl2::render::Texture>,
     positions: Vec<SubTexturePosition>,
+    slices: Vec<Slice>,
+    frame_tags: Vec<FrameTag>,
 }
 
-/// load image texture from JSON metadata
-/// the JSON metadata may be generated from Aseprite.
-pub fn load_from_json(
-    texture_creator: &TextureCreator<WindowContext>,
-    path: &Path,
-) -> Result<Texture, Error> {
-    let meta_str = fs::read_to_string(path).map_err(|_| Error::FileReadFailure)?;
+/// Insets of a 9-slice, in source pixels.
+/// Corners keep their size, edges and center are filled with `SliceFill`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NineSlice {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+/// How a region is filled when the destination size differs from the source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SliceFill {
+    Stretch,
+    Tile,
+}
+
+/// Image decoded to RGBA pixels, with its sprite sheet metadata.
+/// It holds no sdl2 resources, so it can be decoded on a worker thread
+/// and uploaded with `upload` on the main thread.
+pub(crate) struct DecodedTexture {
+    width: u32,
+    height: u32,
+    /// RGBA32, rows packed without padding
+    pixels: Vec<u8>,
+    positions: Vec<SubTexturePosition>,
+    slices: Vec<Slice>,
+    frame_tags: Vec<FrameTag>,
+}
+
+impl DecodedTexture {
+    /// *width* x *height* checkerboard of two colors, in 8 pixel squares.
+    pub(crate) fn checkerboard(width: u32, height: u32, colors: [Color; 2]) -> Self {
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in 0..height {
+            for x in 0..width {
+                let color = colors[((x / 8 + y / 8) % 2) as usize];
+                pixels.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+            }
+        }
+        Self {
+            width,
+            height,
+            pixels,
+            positions: single_frame(width, height),
+            slices: Vec::new(),
+            frame_tags: Vec::new(),
+        }
+    }
+}
+
+/// decode the texture at the virtual *path*.
+/// when file extension is `.json`, the JSON metadata is decoded together.
+pub(crate) fn decode(vfs: &Vfs, path: &str) -> Result<DecodedTexture, Error> {
+    if path.ends_with(".json") {
+        decode_json(vfs, path)
+    } else {
+        decode_file(vfs, path)
+    }
+}
+
+fn decode_json(vfs: &Vfs, path: &str) -> Result<DecodedTexture, Error> {
+    let meta_str = vfs.read_to_string(path)?;
     let metadata: Metadata =
         serde_json::from_str(meta_str.as_str()).map_err(|_| Error::JSONParseFailure)?;
+    let image = vfs.read(&vfs::join(&vfs::parent(path), &metadata.meta.image))?;
+    let (width, height, pixels) = decode_image(&image)?;
+    Ok(DecodedTexture {
+        width,
+        height,
+        pixels,
+        positions: metadata.frames,
+        slices: metadata.meta.slices,
+        frame_tags: metadata.meta.frameTags,
+    })
+}
+
+fn decode_file(vfs: &Vfs, path: &str) -> Result<DecodedTexture, Error> {
+    let (width, height, pixels) = decode_image(&vfs.read(path)?)?;
+    Ok(DecodedTexture {
+        width,
+        height,
+        pixels,
+        positions: single_frame(width, height),
+        slices: Vec::new(),
+        frame_tags: Vec::new(),
+    })
+}
 
-    let sdl_texture = texture_creator
-        .load_texture(path.parent().unwrap().join(metadata.meta.image))
+/// decode image file contents to packed RGBA32 pixels.
+fn decode_image(bytes: &[u8]) -> Result<(u32, u32, Vec<u8>), Error> {
+    let surface = RWops::from_bytes(bytes)
+        .and_then(|rwops| rwops.load())
+        .and_then(|surface| surface.convert_format(PixelFormatEnum::RGBA32))
+        .map_err(|_| Error::TextureCreateFailure)?;
+    let (width, height) = surface.size();
+    let pitch = surface.pitch() as usize;
+    let row = width as usize * 4;
+    let pixels = surface
+        .without_lock()
+        .ok_or(Error::TextureCreateFailure)?
+        .chunks(pitch)
+        .take(height as usize)
+        .flat_map(|line| &line[..row])
+        .copied()
+        .collect();
+    Ok((width, height, pixels))
+}
+
+/// upload *decoded* to the GPU. must be called on the main thread.
+pub(crate) fn upload(
+    texture_creator: &TextureCreator<WindowContext>,
+    decoded: DecodedTexture,
+) -> Result<Texture, Error> {
+    let mut sdl_texture = texture_creator
+        .create_texture_static(PixelFormatEnum::RGBA32, decoded.width, decoded.height)
+        .map_err(|_| Error::TextureCreateFailure)?;
+    sdl_texture
+        .update(None, &decoded.pixels, decoded.width as usize * 4)
         .map_err(|_| Error::TextureCreateFailure)?;
+    sdl_texture.set_blend_mode(sdl2::render::BlendMode::Blend);
 
     Ok(Rc::new(TextureInner {
         sdl_texture: Rc::new(sdl_texture),
-        positions: metadata.frames,
+        positions: decoded.positions,
+        slices: decoded.slices,
+        frame_tags: decoded.frame_tags,
     }))
 }
 
-/// load image texture that does not have JSON metadata.
+/// load image texture from JSON metadata at the virtual *path*.
+/// the JSON metadata may be generated from Aseprite.
+/// the image is looked up next to the JSON file.
+pub fn load_from_json(
+    texture_creator: &TextureCreator<WindowContext>,
+    vfs: &Vfs,
+    path: &str,
+) -> Result<Texture, Error> {
+    upload(texture_creator, decode_json(vfs, path)?)
+}
+
+/// load image texture that does not have JSON metadata, from the virtual *path*.
 pub fn load_from_file(
     texture_creator: &TextureCreator<WindowContext>,
-    path: &Path,
+    vfs: &Vfs,
+    path: &str,
 ) -> Result<Texture, Error> {
-    let sdl_texture = texture_creator
-        .load_texture(path)
-        .map_err(|_| Error::TextureCreateFailure)?;
+    upload(texture_creator, decode_file(vfs, path)?)
+}
+
+/// the whole image as the only frame.
+fn single_frame(width: u32, height: u32) -> Vec<SubTexturePosition> {
+    let rect = Rect {
+        x: 0,
+        y: 0,
+        w: width as i32,
+        h: height as i32,
+    };
+    vec![SubTexturePosition {
+        frame: rect,
+        spriteSourceSize: rect,
+        sourceSize: Size {
+            w: width as i32,
+            h: height as i32,
+        },
+    }]
+}
+
+/// wrap sdl2 texture as a single-frame texture.
+pub(crate) fn from_sdl_texture(sdl_texture: sdl2::render::Texture) -> Texture {
     let sdl2::render::TextureQuery { width, height, .. } = sdl_texture.query();
-    Ok(Rc::new(TextureInner {
+    Rc::new(TextureInner {
         sdl_texture: Rc::new(sdl_texture),
-        positions: vec![SubTexturePosition {
-            frame: Rect {
-                x: 0,
-                y: 0,
-                w: width as i32,
-                h: height as i32,
-            },
-            spriteSourceSize: Rect {
-                x: 0,
-                y: 0,
-                w: width as i32,
-                h: height as i32,
-            },
-            sourceSize: Size {
-                w: width as i32,
-                h: height as i32,
-            },
-        }],
-    }))
+        positions: single_frame(width, height),
+        slices: Vec::new(),
+        frame_tags: Vec::new(),
+    })
+}
+
+/// Free the GPU memory of *texture* if nothing else refers to it.
+/// sdl2 textures are never freed on drop with the `unsafe_textures` feature.
+///
+/// # Safety
+/// *texture* must not be drawn after this returns `true`.
+pub(crate) unsafe fn destroy_if_unique(texture: &mut Texture) -> bool {
+    let Some(inner) = Rc::get_mut(texture) else {
+        return false;
+    };
+    let Some(sdl_texture) = Rc::get_mut(&mut inner.sdl_texture) else {
+        return false;
+    };
+    sdl2::sys::SDL_DestroyTexture(sdl_texture.raw());
+    true
 }
 
 impl TextureInner {
@@ -130,26 +309,7 @@ impl TextureInner {
         if self.positions.is_empty() {
             self.draw(canvas, rect);
         } else {
-            let texture_position: &SubTexturePosition = self
-                .positions
-                .get(idx)
-                .expect(format!("texture index {idx:} not found").as_str());
-            let from_rect: Rect = texture_position.frame;
-
-            let width_ratio: f32 = rect.w as f32 / texture_position.sourceSize.w as f32;
-            let height_ratio: f32 = rect.h as f32 / texture_position.sourceSize.h as f32;
-            let to_rect = Rect {
-                x: rect.x
-                    + (texture_position.spriteSourceSize.x as f32 * width_ratio).round() as i32,
-                y: rect.y
-                    + (texture_position.spriteSourceSize.y as f32 * height_ratio).round() as i32,
-                w: (texture_position.spriteSourceSize.w as f32 * width_ratio).round() as i32,
-                h: (texture_position.spriteSourceSize.h as f32 * height_ratio).round() as i32,
-            };
-
-            // Debug: render region rectangle
-            canvas.set_draw_color(sdl2::pixels::Color::RGB(0, 0, 255));
-            canvas.draw_rect(to_rect.clone().into()).unwrap();
+            let (from_rect, to_rect) = self.frame_rects(rect, idx);
 
             // draw
             canvas
@@ -162,7 +322,208 @@ impl TextureInner {
         }
     }
 
+    /// Get the source region and the trimmed destination of the frame *idx*
+    /// when it is drawn into *rect*.
+    pub fn frame_rects(&self, rect: Rect, idx: usize) -> (Rect, Rect) {
+        if self.positions.is_empty() {
+            return (self.frame(idx), rect);
+        }
+        let texture_position: &SubTexturePosition = self
+            .positions
+            .get(idx)
+            .expect(format!("texture index {idx:} not found").as_str());
+        let from_rect: Rect = texture_position.frame;
+
+        let width_ratio: f32 = rect.w as f32 / texture_position.sourceSize.w as f32;
+        let height_ratio: f32 = rect.h as f32 / texture_position.sourceSize.h as f32;
+        let to_rect = Rect {
+            x: rect.x + (texture_position.spriteSourceSize.x as f32 * width_ratio).round() as i32,
+            y: rect.y + (texture_position.spriteSourceSize.y as f32 * height_ratio).round() as i32,
+            w: (texture_position.spriteSourceSize.w as f32 * width_ratio).round() as i32,
+            h: (texture_position.spriteSourceSize.h as f32 * height_ratio).round() as i32,
+        };
+        (from_rect, to_rect)
+    }
+
+    pub(crate) fn sdl_texture(&self) -> &Rc<sdl2::render::Texture> {
+        &self.sdl_texture
+    }
+
+    /// Draw the frame *idx* as 9-slice.
+    /// Corners are drawn in source size, edges and center are filled with *fill*.
+    /// The insets are applied to the whole frame, so the frame should not be trimmed.
+    /// - *canvas* : the canvas to draw.
+    /// - *rect* : position and size to be drawn in screen, pixel.
+    /// - *idx* : the frame index to draw. starts from 0.
+    pub fn draw_nine_slice(
+        &self,
+        canvas: Canvas,
+        rect: Rect,
+        idx: usize,
+        slice: NineSlice,
+        fill: SliceFill,
+    ) {
+        let frame = self.frame(idx);
+
+        // shrink corners when the destination is smaller than both corners
+        let left = slice.left.min(rect.w / 2);
+        let right = slice.right.min(rect.w - left);
+        let top = slice.top.min(rect.h / 2);
+        let bottom = slice.bottom.min(rect.h - top);
+
+        let from_x = [
+            frame.x,
+            frame.x + slice.left,
+            frame.x + frame.w - slice.right,
+            frame.x + frame.w,
+        ];
+        let from_y = [
+            frame.y,
+            frame.y + slice.top,
+            frame.y + frame.h - slice.bottom,
+            frame.y + frame.h,
+        ];
+        let to_x = [
+            rect.x,
+            rect.x + left,
+            rect.x + rect.w - right,
+            rect.x + rect.w,
+        ];
+        let to_y = [
+            rect.y,
+            rect.y + top,
+            rect.y + rect.h - bottom,
+            rect.y + rect.h,
+        ];
+
+        for row in 0..3 {
+            for col in 0..3 {
+                let from_rect = Rect {
+                    x: from_x[col],
+                    y: from_y[row],
+                    w: from_x[col + 1] - from_x[col],
+                    h: from_y[row + 1] - from_y[row],
+                };
+                let to_rect = Rect {
+                    x: to_x[col],
+                    y: to_y[row],
+                    w: to_x[col + 1] - to_x[col],
+                    h: to_y[row + 1] - to_y[row],
+                };
+                // corners are never tiled
+                let cell_fill = if row != 1 && col != 1 {
+                    SliceFill::Stretch
+                } else {
+                    fill
+                };
+                self.fill_region(canvas, from_rect, to_rect, from_rect.size(), cell_fill);
+            }
+        }
+    }
+
+    /// Fill *rect* by repeating the frame *idx*.
+    /// Tiles on the right and bottom edge are cut.
+    /// - *canvas* : the canvas to draw.
+    /// - *rect* : position and size to be drawn in screen, pixel.
+    /// - *idx* : the frame index to draw. starts from 0.
+    /// - *tile_size* : size of a single tile in screen, pixel.
+    pub fn draw_tiled(&self, canvas: Canvas, rect: Rect, idx: usize, tile_size: Vec2) {
+        self.fill_region(canvas, self.frame(idx), rect, tile_size, SliceFill::Tile);
+    }
+
+    /// Get 9-slice insets of the slice named *name*.
+    /// Returns `None` if there is no such slice or it is not a 9-slice.
+    pub fn nine_slice(&self, name: &str) -> Option<NineSlice> {
+        let slice = self.slices.iter().find(|slice| slice.name == name)?;
+        let key = slice.keys.first()?;
+        let center = key.center?;
+        Some(NineSlice {
+            left: center.x,
+            top: center.y,
+            right: key.bounds.w - center.x - center.w,
+            bottom: key.bounds.h - center.y - center.h,
+        })
+    }
+
+    /// Frames of the animation tag named *name*, first to last.
+    pub fn frame_tag(&self, name: &str) -> Option<RangeInclusive<usize>> {
+        let tag = self.frame_tags.iter().find(|tag| tag.name == name)?;
+        Some(tag.from..=tag.to)
+    }
+
+    /// region of the frame *idx* in the source image
+    fn frame(&self, idx: usize) -> Rect {
+        match self.positions.get(idx) {
+            Some(position) => position.frame,
+            None => Rect::from_start_size(Vec2::default(), self.size()),
+        }
+    }
+
+    /// copy *from_rect* of the source image into *to_rect*.
+    /// when tiling, *from_rect* is repeated in *tile_size*.
+    fn fill_region(
+        &self,
+        canvas: Canvas,
+        from_rect: Rect,
+        to_rect: Rect,
+        tile_size: Vec2,
+        fill: SliceFill,
+    ) {
+        if from_rect.w <= 0 || from_rect.h <= 0 || to_rect.w <= 0 || to_rect.h <= 0 {
+            return;
+        }
+        if fill == SliceFill::Stretch || tile_size.x <= 0 || tile_size.y <= 0 {
+            canvas
+                .copy::<sdl2::rect::Rect, sdl2::rect::Rect>(
+                    &self.sdl_texture,
+                    from_rect.into(),
+                    to_rect.into(),
+                )
+                .unwrap();
+            return;
+        }
+
+        let mut y = to_rect.y;
+        while y < to_rect.y + to_rect.h {
+            let h = tile_size.y.min(to_rect.y + to_rect.h - y);
+            let mut x = to_rect.x;
+            while x < to_rect.x + to_rect.w {
+                let w = tile_size.x.min(to_rect.x + to_rect.w - x);
+
+                // cut the source proportionally for partial tiles
+                let from_tile = Rect {
+                    x: from_rect.x,
+                    y: from_rect.y,
+                    w: (from_rect.w * w / tile_size.x).max(1),
+                    h: (from_rect.h * h / tile_size.y).max(1),
+                };
+                canvas
+                    .copy::<sdl2::rect::Rect, sdl2::rect::Rect>(
+                        &self.sdl_texture,
+                        from_tile.into(),
+                        Rect { x, y, w, h }.into(),
+                    )
+                    .unwrap();
+                x += tile_size.x;
+            }
+            y += tile_size.y;
+        }
+    }
+
+    /// size of the whole source image, pixel.
+    pub fn size(&self) -> Vec2 {
+        let sdl2::render::TextureQuery { width, height, .. } = self.sdl_texture.query();
+        Vec2 {
+            x: width as i32,
+            y: height as i32,
+        }
+    }
+
     pub fn len(&self) -> usize {
         self.positions.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
 }
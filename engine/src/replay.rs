@@ -0,0 +1,225 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::game::scene::Scene;
+use crate::input::Button;
+use crate::types::*;
+
+/// version of the recording file format
+const RECORDING_VERSION: u32 = 1;
+
+/// Input of one tick. Everything that affects the scene goes through it,
+/// so feeding the same frames to the same scene gives the same result.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct InputFrame {
+    /// frame time passed to `Scene::update`
+    pub delta: Duration,
+    /// key presses and releases by SDL key name, in order
+    pub key_events: Vec<(String, bool)>,
+    /// keys held down, by SDL key name
+    pub held_keys: Vec<String>,
+    pub mouse_wheel: f32,
+    /// left stick of the first player
+    pub stick: (f32, f32),
+    /// buttons held by the first player, by SDL button name
+    pub buttons: Vec<String>,
+}
+
+impl InputFrame {
+    fn is_button_down(&self, button: Button) -> bool {
+        self.buttons.contains(&button.string())
+    }
+}
+
+/// Apply *input* to *scene*. It does not update the scene.
+pub(crate) fn apply_input(scene: &mut Scene, input: &InputFrame) {
+    for (key, pressed) in input.key_events.iter() {
+        if *pressed && key == "Space" && !scene.entities().is_empty() {
+            scene.toggle_camera_attachment();
+        }
+        scene.key_event(key, *pressed);
+    }
+    if input.mouse_wheel != 0.0 {
+        scene.add_zoom(input.mouse_wheel);
+    }
+
+    // WASD, first player's left stick and d-pad move the first entity
+    let mut movement = Vec2 {
+        x: (input.stick.0 * 10.0).round() as i32,
+        y: (input.stick.1 * 10.0).round() as i32,
+    };
+    for key in input.held_keys.iter() {
+        match key.as_str() {
+            "W" => movement = movement + Vec2 { x: 0, y: 10 },
+            "A" => movement = movement + Vec2 { x: -10, y: 0 },
+            "S" => movement = movement + Vec2 { x: 0, y: -10 },
+            "D" => movement = movement + Vec2 { x: 10, y: 0 },
+            _ => (),
+        }
+    }
+    for (button, direction) in [
+        (Button::DPadUp, Vec2 { x: 0, y: 10 }),
+        (Button::DPadLeft, Vec2 { x: -10, y: 0 }),
+        (Button::DPadDown, Vec2 { x: 0, y: -10 }),
+        (Button::DPadRight, Vec2 { x: 10, y: 0 }),
+    ] {
+        if input.is_button_down(button) {
+            movement = movement + direction;
+        }
+    }
+    if (movement.x != 0 || movement.y != 0) && !scene.entities().is_empty() {
+        scene.set_position(scene.get_position() + movement);
+    }
+}
+
+/// Recorded input of a session with the seed it was played with.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct Recording {
+    pub version: u32,
+    pub seed: u64,
+    pub frames: Vec<InputFrame>,
+}
+
+impl Recording {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            version: RECORDING_VERSION,
+            seed,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let json = fs::read_to_string(path).map_err(|_| Error::FileReadFailure)?;
+        let recording: Recording =
+            serde_json::from_str(&json).map_err(|_| Error::JSONParseFailure)?;
+        if recording.version != RECORDING_VERSION {
+            return Err(Error::UnsupportedVersion(recording.version));
+        }
+        Ok(recording)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let json = serde_json::to_string(self).map_err(|_| Error::JSONParseFailure)?;
+        fs::write(path, json).map_err(|_| Error::FileWriteFailure)
+    }
+}
+
+/// Runs a scene without a window, for verifying recordings in tests.
+///
+/// ```ignore
+/// let recording = Recording::load(Path::new("bug_report.json"))?;
+/// let mut runner = HeadlessRunner::new(build_scene());
+/// assert_eq!(runner.run(&recording), expected_hash);
+/// ```
+pub struct HeadlessRunner {
+    pub scene: Scene,
+    ticks: usize,
+}
+
+impl HeadlessRunner {
+    pub fn new(scene: Scene) -> Self {
+        Self { scene, ticks: 0 }
+    }
+
+    /// Apply *input* and update the scene by one tick.
+    pub fn step(&mut self, input: &InputFrame) {
+        apply_input(&mut self.scene, input);
+        self.scene.update(input.delta);
+        self.ticks += 1;
+    }
+
+    /// Seed the scene with the recorded seed, play every frame of *recording*
+    /// and return the final `Scene::state_hash`.
+    pub fn run(&mut self, recording: &Recording) -> u64 {
+        self.scene.set_seed(recording.seed);
+        for input in recording.frames.iter() {
+            self.step(input);
+        }
+        self.scene.state_hash()
+    }
+
+    /// number of ticks run so far
+    pub fn ticks(&self) -> usize {
+        self.ticks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::game::entity::{EntityDrawInfo, EntityTrait};
+
+    /// entity without a sprite, it only has bounds
+    struct Dot {
+        pos: Vec2,
+    }
+
+    impl EntityTrait for Dot {
+        fn pos(&self) -> Vec2 {
+            self.pos
+        }
+
+        fn set_pos(&mut self, new_pos: Vec2) {
+            self.pos = new_pos;
+        }
+
+        fn update(&mut self) {}
+
+        fn get_draw_info(&self) -> EntityDrawInfo {
+            unreachable!("headless entities are never drawn")
+        }
+
+        fn bounds(&self) -> Option<Rect> {
+            Some(Rect::from_center_size(self.pos, Vec2 { x: 16, y: 16 }))
+        }
+    }
+
+    fn build_scene() -> Scene {
+        let mut scene = Scene::new();
+        scene.add_entity(Rc::new(RefCell::new(Dot {
+            pos: Vec2 { x: 0, y: 0 },
+        })));
+        scene
+    }
+
+    fn build_recording() -> Recording {
+        let mut recording = Recording::new(42);
+        for idx in 0..60 {
+            let key = if idx % 20 < 10 { "D" } else { "W" };
+            recording.frames.push(InputFrame {
+                delta: Duration::from_millis(16),
+                held_keys: vec![key.to_string()],
+                ..InputFrame::default()
+            });
+        }
+        recording
+    }
+
+    #[test]
+    fn same_recording_gives_same_hash() {
+        let recording = build_recording();
+        let first = HeadlessRunner::new(build_scene()).run(&recording);
+        let second = HeadlessRunner::new(build_scene()).run(&recording);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn changed_input_changes_hash() {
+        let recording = build_recording();
+        let mut changed = recording.clone();
+        changed.frames[30].held_keys = vec!["S".to_string()];
+
+        let mut runner = HeadlessRunner::new(build_scene());
+        let hash = runner.run(&recording);
+        assert_eq!(runner.ticks(), recording.frames.len());
+        assert_ne!(hash, HeadlessRunner::new(build_scene()).run(&changed));
+    }
+}
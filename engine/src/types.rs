@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default)]
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
 pub struct Rect {
     pub x: i32,
     pub y: i32,
@@ -91,7 +91,50 @@ impl Into<sdl2::rect::Rect> for Rect {
     }
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default)]
+/// RGBA color.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const RED: Color = Color::rgb(255, 0, 0);
+    pub const GREEN: Color = Color::rgb(0, 255, 0);
+    pub const BLUE: Color = Color::rgb(0, 0, 255);
+    pub const MAGENTA: Color = Color::rgb(255, 0, 255);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: 255 }
+    }
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Linear interpolation to *other*. *t* is 0.0 to 1.0.
+    pub fn lerp(self, other: Color, t: f32) -> Color {
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Color {
+            r: mix(self.r, other.r),
+            g: mix(self.g, other.g),
+            b: mix(self.b, other.b),
+            a: mix(self.a, other.a),
+        }
+    }
+}
+
+impl From<Color> for sdl2::pixels::Color {
+    fn from(color: Color) -> Self {
+        sdl2::pixels::Color::RGBA(color.r, color.g, color.b, color.a)
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
 pub struct Vec2 {
     pub x: i32,
     pub y: i32,
@@ -169,3 +212,80 @@ impl std::ops::Div<i32> for Vec2 {
         }
     }
 }
+
+/// Position, rotation and scale in world space y-up coordinates.
+/// Rotation is in degrees, counterclockwise.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Transform {
+    pub x: f32,
+    pub y: f32,
+    pub rotation: f32,
+    pub scale: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        x: 0.0,
+        y: 0.0,
+        rotation: 0.0,
+        scale: 1.0,
+    };
+
+    pub fn from_position(position: Vec2) -> Self {
+        Self {
+            x: position.x as f32,
+            y: position.y as f32,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// position rounded to world units
+    pub fn position(&self) -> Vec2 {
+        Vec2 {
+            x: self.x.round() as i32,
+            y: self.y.round() as i32,
+        }
+    }
+
+    /// Transform *point* from the local space of this transform.
+    pub fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        (
+            self.x + (x * cos - y * sin) * self.scale,
+            self.y + (x * sin + y * cos) * self.scale,
+        )
+    }
+
+    /// *local* placed in the space of this transform.
+    pub fn compose(&self, local: Transform) -> Transform {
+        let (x, y) = self.apply(local.x, local.y);
+        Transform {
+            x,
+            y,
+            rotation: self.rotation + local.rotation,
+            scale: self.scale * local.scale,
+        }
+    }
+
+    /// `self.compose(self.inverse())` is the identity.
+    pub fn inverse(&self) -> Transform {
+        let scale = if self.scale == 0.0 {
+            0.0
+        } else {
+            1.0 / self.scale
+        };
+        let (sin, cos) = (-self.rotation).to_radians().sin_cos();
+        Transform {
+            x: -(self.x * cos - self.y * sin) * scale,
+            y: -(self.x * sin + self.y * cos) * scale,
+            rotation: -self.rotation,
+            scale,
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use crate::error::Error;
+
+pub mod pack;
+
+pub use pack::PackMount;
+
+/// Source of files mounted into a `Vfs`.
+/// Paths are relative to the mount point, separated by `/`.
+pub trait Mount: Send + Sync {
+    fn exists(&self, path: &str) -> bool;
+    fn read(&self, path: &str) -> Result<Vec<u8>, Error>;
+    /// Every file, in no particular order.
+    fn files(&self) -> Vec<String>;
+}
+
+/// Directory on disk.
+pub struct DirMount {
+    root: PathBuf,
+}
+
+impl DirMount {
+    pub fn new(root: &Path) -> Result<Self, Error> {
+        if !root.is_dir() {
+            return Err(Error::FileReadFailure);
+        }
+        Ok(Self {
+            root: root.to_path_buf(),
+        })
+    }
+}
+
+impl Mount for DirMount {
+    fn exists(&self, path: &str) -> bool {
+        self.root.join(path).is_file()
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        fs::read(self.root.join(path)).map_err(|_| Error::FileReadFailure)
+    }
+
+    fn files(&self) -> Vec<String> {
+        let mut files = Vec::new();
+        let mut directories = vec![self.root.clone()];
+        while let Some(directory) = directories.pop() {
+            let Ok(entries) = fs::read_dir(&directory) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    directories.push(path);
+                } else if let Ok(relative) = path.strip_prefix(&self.root) {
+                    files.push(normalize(&relative.to_string_lossy()));
+                }
+            }
+        }
+        files
+    }
+}
+
+/// Files compiled into the binary with `include_bytes!`.
+pub struct EmbeddedMount {
+    files: HashMap<String, &'static [u8]>,
+}
+
+impl EmbeddedMount {
+    pub fn new(files: &[(&str, &'static [u8])]) -> Self {
+        Self {
+            files: files
+                .iter()
+                .map(|(path, contents)| (normalize(path), *contents))
+                .collect(),
+        }
+    }
+}
+
+impl Mount for EmbeddedMount {
+    fn exists(&self, path: &str) -> bool {
+        self.files.contains_key(path)
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        self.files
+            .get(path)
+            .map(|contents| contents.to_vec())
+            .ok_or(Error::FileReadFailure)
+    }
+
+    fn files(&self) -> Vec<String> {
+        self.files.keys().cloned().collect()
+    }
+}
+
+/// Virtual file system that every asset loader reads from.
+///
+/// Sources are mounted at a virtual directory such as `assets`.
+/// When several mounts have the same file, the one mounted last wins,
+/// so loose files can override a pack during development.
+#[derive(Default)]
+pub struct Vfs {
+    /// mount point and source, in the order mounted
+    mounts: RwLock<Vec<(String, Arc<dyn Mount>)>>,
+}
+
+impl Vfs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The engine font embedded in the binary, then `assets.pack` and the `assets` directory
+    /// from the working directory or the directory of the executable, mounted at `assets`.
+    pub fn with_default_mounts() -> Self {
+        let vfs = Self::new();
+        vfs.mount(
+            "assets",
+            EmbeddedMount::new(&[
+                ("font.json", include_bytes!("../../assets/font.json")),
+                ("font.png", include_bytes!("../../assets/font.png")),
+            ]),
+        );
+
+        let mut bases = vec![PathBuf::from(".")];
+        if let Some(exe_dir) = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf))
+        {
+            bases.push(exe_dir);
+        }
+        if let Some(pack) = bases
+            .iter()
+            .map(|base| base.join("assets.pack"))
+            .find(|path| path.is_file())
+        {
+            if let Err(err) = vfs.mount_pack("assets", &pack) {
+                println!("failed to mount {pack:?} : {err:?}");
+            }
+        }
+        if let Some(directory) = bases
+            .iter()
+            .map(|base| base.join("assets"))
+            .find(|path| path.is_dir())
+        {
+            let _ = vfs.mount_dir("assets", &directory);
+        }
+        vfs
+    }
+
+    /// Mount *mount* at the virtual directory *point*. Use `""` for the root.
+    pub fn mount(&self, point: &str, mount: impl Mount + 'static) {
+        self.mounts
+            .write()
+            .unwrap()
+            .push((normalize(point), Arc::new(mount)));
+    }
+
+    pub fn mount_dir(&self, point: &str, directory: &Path) -> Result<(), Error> {
+        self.mount(point, DirMount::new(directory)?);
+        Ok(())
+    }
+
+    pub fn mount_pack(&self, point: &str, path: &Path) -> Result<(), Error> {
+        self.mount(point, PackMount::open(path)?);
+        Ok(())
+    }
+
+    /// Remove every mount at *point*.
+    pub fn unmount(&self, point: &str) {
+        let point = normalize(point);
+        self.mounts
+            .write()
+            .unwrap()
+            .retain(|(mount_point, _)| *mount_point != point);
+    }
+
+    /// Mount that has *path*, and the path inside it.
+    fn resolve(&self, path: &str) -> Option<(Arc<dyn Mount>, String)> {
+        let path = normalize(path);
+        let mounts = self.mounts.read().unwrap();
+        mounts.iter().rev().find_map(|(point, mount)| {
+            let relative = if point.is_empty() {
+                path.as_str()
+            } else {
+                path.strip_prefix(point.as_str())?.strip_prefix('/')?
+            };
+            mount
+                .exists(relative)
+                .then(|| (mount.clone(), relative.to_string()))
+        })
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.resolve(path).is_some()
+    }
+
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        let (mount, relative) = self.resolve(path).ok_or(Error::FileReadFailure)?;
+        mount.read(&relative)
+    }
+
+    pub fn read_to_string(&self, path: &str) -> Result<String, Error> {
+        String::from_utf8(self.read(path)?).map_err(|_| Error::FileReadFailure)
+    }
+
+    /// Every visible file, sorted.
+    pub fn files(&self) -> Vec<String> {
+        let mounts = self.mounts.read().unwrap();
+        let mut files: Vec<String> = mounts
+            .iter()
+            .flat_map(|(point, mount)| {
+                mount
+                    .files()
+                    .into_iter()
+                    .map(move |file| join(point, &file))
+            })
+            .collect();
+        files.sort();
+        files.dedup();
+        files
+    }
+}
+
+/// Join virtual paths.
+pub fn join(directory: &str, path: &str) -> String {
+    normalize(&format!("{directory}/{path}"))
+}
+
+/// Parent directory of a virtual path, `""` at the root.
+pub fn parent(path: &str) -> String {
+    let path = normalize(path);
+    match path.rfind('/') {
+        Some(idx) => path[..idx].to_string(),
+        None => String::new(),
+    }
+}
+
+/// Virtual path with `/` separators and without `.`, `..` and empty parts.
+/// `..` can't go above the root.
+pub fn normalize(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split(['/', '\\']) {
+        match part {
+            "" | "." => (),
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Mutex;
+
+pub use asset_pack::{hash, PackEntry, PackManifest, PACK_VERSION};
+use asset_pack::{HEADER_LEN, MAGIC};
+
+use crate::error::Error;
+use crate::vfs::Mount;
+
+/// Pack archive written by the `pack` tool of the `asset-pack` crate.
+///
+/// Layout: `EPAK`, version (u32 LE), manifest length (u32 LE), manifest JSON, file data.
+/// Files are read on demand and checked against their hash.
+pub struct PackMount {
+    file: Mutex<fs::File>,
+    data_start: u64,
+    entries: HashMap<String, PackEntry>,
+}
+
+impl PackMount {
+    /// Open the pack at *path*. Sizes in the header and the manifest are checked
+    /// against the file length, so a broken pack fails here instead of allocating them.
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let mut file = fs::File::open(path).map_err(|_| Error::FileReadFailure)?;
+        let file_len = file.metadata().map_err(|_| Error::FileReadFailure)?.len();
+        let mut header = [0u8; HEADER_LEN as usize];
+        file.read_exact(&mut header)
+            .map_err(|_| Error::FileReadFailure)?;
+        if &header[0..4] != MAGIC {
+            return Err(Error::FileReadFailure);
+        }
+        let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        if version != PACK_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let manifest_len = u32::from_le_bytes(header[8..12].try_into().unwrap()) as u64;
+        let data_start = HEADER_LEN + manifest_len;
+        if data_start > file_len {
+            return Err(Error::FileReadFailure);
+        }
+
+        let mut manifest = vec![0u8; manifest_len as usize];
+        file.read_exact(&mut manifest)
+            .map_err(|_| Error::FileReadFailure)?;
+        let manifest: PackManifest =
+            serde_json::from_slice(&manifest).map_err(|_| Error::JSONParseFailure)?;
+
+        let data_len = file_len - data_start;
+        let in_bounds = |entry: &PackEntry| {
+            entry
+                .offset
+                .checked_add(entry.size)
+                .is_some_and(|end| end <= data_len)
+        };
+        if let Some(entry) = manifest.files.iter().find(|entry| !in_bounds(entry)) {
+            println!("pack entry {} is out of the file", entry.path);
+            return Err(Error::FileReadFailure);
+        }
+
+        Ok(Self {
+            file: Mutex::new(file),
+            data_start,
+            entries: manifest
+                .files
+                .into_iter()
+                .map(|entry| (entry.path.clone(), entry))
+                .collect(),
+        })
+    }
+
+    pub fn entry(&self, path: &str) -> Option<&PackEntry> {
+        self.entries.get(path)
+    }
+}
+
+impl Mount for PackMount {
+    fn exists(&self, path: &str) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        // sizes were checked against the file length in `open`
+        let entry = self.entries.get(path).ok_or(Error::FileReadFailure)?;
+        let mut stored = vec![0u8; entry.size as usize];
+        {
+            let mut file = self.file.lock().unwrap();
+            file.seek(SeekFrom::Start(self.data_start + entry.offset))
+                .map_err(|_| Error::FileReadFailure)?;
+            file.read_exact(&mut stored)
+                .map_err(|_| Error::FileReadFailure)?;
+        }
+
+        let contents = if entry.compressed {
+            miniz_oxide::inflate::decompress_to_vec_with_limit(
+                &stored,
+                entry.original_size as usize,
+            )
+            .map_err(|_| Error::HashMismatch(path.to_string()))?
+        } else {
+            stored
+        };
+        if hash(&contents) != entry.hash {
+            return Err(Error::HashMismatch(path.to_string()));
+        }
+        Ok(contents)
+    }
+
+    fn files(&self) -> Vec<String> {
+        self.entries.keys().cloned().collect()
+    }
+}
@@ -1,17 +1,30 @@
 use std::path::Path;
 
 use engine::game::{self, scene::Scene};
+use engine::locale::LocalizedText;
+use engine::render::rich_text::RichFont;
 use engine::types::*;
-use engine::Engine;
+use engine::{Engine, EngineConfig};
 
 fn main() {
     println!("start");
-    let mut engine = Engine::new().unwrap();
+    let config = EngineConfig::load_or_default(Path::new("settings.json")).unwrap();
+    let mut engine = Engine::new(config).unwrap();
+
+    // --record <file> saves the input of this session, --replay <file> plays it back
+    let args: Vec<String> = std::env::args().collect();
+    for pair in args.windows(2) {
+        match pair[0].as_str() {
+            "--record" => engine.record(Path::new(&pair[1])),
+            "--replay" => engine.replay(Path::new(&pair[1])).unwrap(),
+            _ => (),
+        }
+    }
 
     // load textures
-    engine.load_texture("sprite.human", Path::new("assets/human.json"));
-    engine.load_texture("sprite.test", Path::new("assets/test.json"));
-    engine.load_texture("sprite.awesomeface", Path::new("assets/awesomeface_3d.png"));
+    engine.load_texture("sprite.human", "assets/human.json");
+    engine.load_texture("sprite.test", "assets/test.json");
+    engine.load_texture("sprite.awesomeface", "assets/awesomeface_3d.png");
 
     // new scene
     let mut scene0 = Scene::new();
@@ -45,6 +58,18 @@ fn main() {
 
     scene0.add_ui(Box::new(debug_text));
 
+    let font = engine.renderer.fonts.first().unwrap().clone();
+    let mut greeting = game::ui::text::TextElement::localized(
+        font.clone(),
+        engine.localization(),
+        LocalizedText::new("greeting_markup").arg("name", "world"),
+    )
+    .markup(RichFont::new(font))
+    .typewriter(0.5);
+    greeting.pos = Vec2 { x: 30, y: 140 };
+    greeting.scale = Vec2 { x: 25, y: 50 };
+    scene0.add_ui(Box::new(greeting));
+
     engine.add_scene(scene0);
 
     // main loop